@@ -1,13 +1,26 @@
 use crate::{
-    execute::{looper::TaskVariablesList, postaction::execute_command},
-    inquire::inspect,
+    execute::{
+        looper::TaskVariablesList,
+        postaction::execute_command,
+        swarm::{force_update, remove_task},
+    },
+    inquire::{
+        compose::{compose_members, restart_order},
+        events::expect_stop,
+        inspect::{self, summarise_health_log},
+        pod::pod_members,
+    },
     report::{logging::log_message, webhook::notify_webhook},
     ERROR, INFO, WARNING,
 };
-use bollard::container::RestartContainerOptions;
+use bollard::container::{RestartContainerOptions, StopContainerOptions};
 use serde_json::json;
 
-pub async fn execute_tasks(var: TaskVariablesList) -> String {
+// Longest captured log tail included in notifications
+const NOTIFY_TAIL_LIMIT: usize = 1000;
+
+// Remediate, returning the message and outcome (one of ALLOWED_HISTORY_OUTCOMES) to record
+pub async fn execute_tasks(var: TaskVariablesList) -> (String, &'static str) {
     // Prepare reusable objects
     let hostname = var.hostname;
     let docker = var.docker;
@@ -20,6 +33,24 @@ pub async fn execute_tasks(var: TaskVariablesList) -> String {
     let post_action = var.post_action;
     let stop_timeout = var.stop_timeout;
     let restart_enable = var.restart_enable;
+    let pod = var.pod;
+    let service_id = var.service_id;
+    let service_name = var.service_name;
+    let swarm_action = var.swarm_action;
+    let restart_scope = var.restart_scope;
+    let project = var.project;
+    let compose_service = var.service;
+    let action = var.action;
+    let rule = var.rule;
+    let log_tail = var.log_tail;
+    let diagnostics = var.diagnostics;
+    let quiet = var.quiet;
+
+    // Identify the Swarm service alongside the host in notifications
+    let subject = match service_name.is_empty() {
+        true => hostname.clone(),
+        false => format!("{}/{}", hostname, service_name),
+    };
 
     // Report unhealthy container
     let msg0 = format!(
@@ -32,9 +63,227 @@ pub async fn execute_tasks(var: TaskVariablesList) -> String {
         name, id, inspection.exit_code, inspection.failing_reason
     );
     log_message(&msg1, WARNING).await;
+    // Summarise the retained health log alongside the last output in notifications
+    let mut notice = match inspection.health_log.is_empty() {
+        true => msg1.clone(),
+        false => {
+            let summary = summarise_health_log(&inspection.health_log);
+            let msg2 = format!("[{} ({})] Container health log: {}", name, id, summary);
+            log_message(&msg2, WARNING).await;
+            format!("{}; {}", msg1, summary)
+        }
+    };
+    // Reference the diagnostics bundle captured before acting
+    if !diagnostics.is_empty() {
+        notice = format!("{}; diagnostics {}", notice, diagnostics);
+    }
+    // Include the most recent captured log lines, truncated for notifications
+    if !log_tail.is_empty() {
+        let mut tail = String::new();
+        for line in log_tail.iter().rev() {
+            if tail.len() + line.len() + 1 > NOTIFY_TAIL_LIMIT {
+                break;
+            }
+            tail = format!("{}\n{}", line, tail);
+        }
+        notice = format!("{}\n{}", notice, tail.trim_end());
+    }
 
     let mut msg = format!("Restart enabled: {}", restart_enable);
-    if restart_enable {
+    let mut outcome = match restart_enable {
+        true => "other",
+        false => "disabled",
+    };
+    // Gather pod members when the whole pod is to be restarted
+    let members = match restart_enable && !pod.is_empty() {
+        true => pod_members(&docker, &pod).await,
+        false => vec![],
+    };
+    // Gather compose project members when dependants or the whole project are to be restarted
+    let group = match restart_enable && restart_scope != "container" && !project.is_empty() {
+        true => restart_order(
+            compose_members(&docker, &project).await,
+            &compose_service,
+            &restart_scope,
+        ),
+        false => vec![],
+    };
+    if restart_enable && action == "notify" {
+        msg = format!(
+            "[{} ({})] Container restart skipped on request as {}",
+            name, id, rule
+        );
+        log_message(&msg, WARNING).await;
+    } else if restart_enable && action == "stop" {
+        // Report container stopping
+        let msg0 = format!(
+            "[{} ({})] Container stopping with {}s timeout as {}",
+            name, id, stop_timeout, rule
+        );
+        log_message(&msg0, WARNING).await;
+        let stop_options = Some(StopContainerOptions {
+            t: stop_timeout as i64,
+        });
+        // Keep this stop apart from those requested by users
+        expect_stop(&id);
+        msg = match &docker.stop_container(&id, stop_options).await {
+            Ok(()) => {
+                outcome = "success";
+                let msg0 = format!("[{} ({})] Container stop was successful", name, id);
+                log_message(&msg0, INFO).await;
+                msg0
+            }
+            Err(e) => {
+                outcome = "failed";
+                let msg0 = format!("[{} ({})] Container stop failed: {}", name, id, e);
+                log_message(&msg0, ERROR).await;
+                msg0
+            }
+        };
+    } else if restart_enable && !service_id.is_empty() && swarm_action != "restart" {
+        // Leave rescheduling to the orchestrator rather than restarting the task directly
+        let via = match swarm_action.as_str() {
+            "update" => "forced update",
+            _ => "task removal",
+        };
+        let msg0 = format!(
+            "[{} ({})] Service {} restarting via {}",
+            name, id, service_name, via
+        );
+        log_message(&msg0, WARNING).await;
+        let result = match swarm_action.as_str() {
+            "update" => force_update(&docker, &service_id).await,
+            _ => remove_task(&docker, &id).await,
+        };
+        msg = match result {
+            Ok(()) => {
+                outcome = "success";
+                let msg0 = format!(
+                    "[{} ({})] Service {} restart was successful via {}",
+                    name, id, service_name, via
+                );
+                log_message(&msg0, INFO).await;
+                msg0
+            }
+            Err(e) => {
+                outcome = "failed";
+                let msg0 = format!(
+                    "[{} ({})] Service {} restart failed via {}: {}",
+                    name, id, service_name, via, e
+                );
+                log_message(&msg0, ERROR).await;
+                msg0
+            }
+        };
+
+        // Execute post-action
+        if !post_action.is_empty() {
+            execute_command(post_action, &name, id.to_string(), stop_timeout.to_string()).await;
+        }
+    } else if restart_enable && !group.is_empty() {
+        // Report project restarting
+        let msg0 = format!(
+            "[{} ({})] Project {} restarting {} containers ({}) with {}s timeout",
+            name,
+            id,
+            project,
+            group.len(),
+            restart_scope,
+            stop_timeout
+        );
+        log_message(&msg0, WARNING).await;
+
+        // Restart in dependency order so dependants come back after what they rely on
+        let mut errors = vec![];
+        for member in &group {
+            let restart_options = Some(RestartContainerOptions { t: stop_timeout });
+            match docker.restart_container(&member.id, restart_options).await {
+                Ok(()) => {
+                    let msg0 = format!(
+                        "[{} ({})] Project {} service {} restarted",
+                        member.name, member.id, project, member.service
+                    );
+                    log_message(&msg0, INFO).await;
+                }
+                Err(e) => errors.push(format!("{}: {}", member.name, e)),
+            }
+        }
+        msg = match errors.is_empty() {
+            true => {
+                outcome = "success";
+                let msg0 = format!(
+                    "[{} ({})] Project {} restart was successful",
+                    name, id, project
+                );
+                log_message(&msg0, INFO).await;
+                msg0
+            }
+            false => {
+                outcome = "failed";
+                let msg0 = format!(
+                    "[{} ({})] Project {} restart failed: {}",
+                    name,
+                    id,
+                    project,
+                    errors.join("; ")
+                );
+                log_message(&msg0, ERROR).await;
+                msg0
+            }
+        };
+
+        // Execute post-action
+        if !post_action.is_empty() {
+            execute_command(post_action, &name, id.to_string(), stop_timeout.to_string()).await;
+        }
+    } else if restart_enable && !members.is_empty() {
+        // Report pod restarting
+        let msg0 = format!(
+            "[{} ({})] Pod {} restarting {} containers with {}s timeout",
+            name,
+            id,
+            pod,
+            members.len(),
+            stop_timeout
+        );
+        log_message(&msg0, WARNING).await;
+
+        // Restart every member of the pod concurrently
+        let restarts = members.iter().map(|(_, member)| {
+            docker.restart_container(member, Some(RestartContainerOptions { t: stop_timeout }))
+        });
+        let errors: Vec<String> = futures::future::join_all(restarts)
+            .await
+            .into_iter()
+            .zip(members.iter())
+            .filter_map(|(r, (member, _))| r.err().map(|e| format!("{}: {}", member, e)))
+            .collect();
+        msg = match errors.is_empty() {
+            true => {
+                outcome = "success";
+                let msg0 = format!("[{} ({})] Pod {} restart was successful", name, id, pod);
+                log_message(&msg0, INFO).await;
+                msg0
+            }
+            false => {
+                outcome = "failed";
+                let msg0 = format!(
+                    "[{} ({})] Pod {} restart failed: {}",
+                    name,
+                    id,
+                    pod,
+                    errors.join("; ")
+                );
+                log_message(&msg0, ERROR).await;
+                msg0
+            }
+        };
+
+        // Execute post-action
+        if !post_action.is_empty() {
+            execute_command(post_action, &name, id.to_string(), stop_timeout.to_string()).await;
+        }
+    } else if restart_enable {
         // Build restart options
         let restart_options = Some(RestartContainerOptions { t: stop_timeout });
 
@@ -53,12 +302,14 @@ pub async fn execute_tasks(var: TaskVariablesList) -> String {
         msg = match &docker.restart_container(&target, restart_options).await {
             Ok(()) => {
                 // Log result
+                outcome = "success";
                 let msg0 = format!("[{} ({})] Container restart was successful", name, id);
                 log_message(&msg0, INFO).await;
                 msg0
             }
             Err(e) => {
                 // Log result
+                outcome = "failed";
                 let msg0 = format!("[{} ({})] Container restart failed: {}", name, id, e);
                 log_message(&msg0, ERROR).await;
                 msg0
@@ -70,10 +321,19 @@ pub async fn execute_tasks(var: TaskVariablesList) -> String {
             execute_command(post_action, &name, id.to_string(), stop_timeout.to_string()).await;
         }
     }
+    // Only failed remediation is notified during quiet hours
+    if quiet && outcome != "failed" {
+        let msg0 = format!(
+            "[{} ({})] Container notification withheld during quiet hours",
+            name, id
+        );
+        log_message(&msg0, INFO).await;
+        return (msg, outcome);
+    }
     // Send webhook
     if !(webhook_url.is_empty() || webhook_key.is_empty()) {
         let payload = json!({
-            webhook_key: format!("{}|{}|{}", hostname, msg1, msg)
+            webhook_key: format!("{}|{}|{}", subject, notice, msg)
         }).to_string();
         notify_webhook(&webhook_url, &payload).await;
     }
@@ -81,9 +341,9 @@ pub async fn execute_tasks(var: TaskVariablesList) -> String {
     if !apprise_url.is_empty() {
         let payload = json!({
             "title": "Docker-Autoheal",
-            "body": format!("{}|{}|{}", hostname, msg1, msg)
+            "body": format!("{}|{}|{}", subject, notice, msg)
         }).to_string();
         notify_webhook(&apprise_url, &payload).await;
     }
-    msg
+    (msg, outcome)
 }
@@ -1,14 +1,118 @@
-use crate::{log_message, ERROR, INFO};
+use crate::{execute::tunnel::open_tunnel, log_message, ERROR, INFO, WARNING};
 use bollard::{Docker, API_DEFAULT_VERSION};
+use std::io::BufReader;
+
+#[derive(Clone)]
+pub struct ConnectionVariablesList {
+    pub connection_type: String,
+    pub tcp_address: String,
+    pub tcp_timeout: u64,
+    pub key_path: String,
+    pub cert_path: String,
+    pub ca_path: String,
+    pub socket_path: String,
+    pub ssh_host: String,
+    pub ssh_key: String,
+    pub ssh_known_hosts: String,
+}
+
+// Whether the Docker host is another machine, whose container addresses are not reachable from here
+pub fn is_remote(var: &ConnectionVariablesList) -> bool {
+    let host = match var.connection_type.as_str() {
+        "local" | "socket" => return false,
+        "ssh" => return true,
+        _ => var
+            .tcp_address
+            .rsplit_once(':')
+            .map_or(var.tcp_address.as_str(), |(h, _)| h)
+            .trim_matches(|c| c == '[' || c == ']'),
+    };
+    !(host == "localhost" || host == "::1" || host.starts_with("127."))
+}
+
+// Determine whether the API is served by Docker or Podman
+pub async fn detect_runtime(docker: &Docker, runtime: &str) -> String {
+    let version = match docker.version().await {
+        Ok(v) => v,
+        Err(e) => {
+            let fallback = match runtime {
+                "podman" => "podman",
+                _ => "docker",
+            };
+            let msg0 = format!(
+                "Could not reliably determine container engine; assuming {}: {}",
+                fallback, e
+            );
+            log_message(&msg0, WARNING).await;
+            return fallback.to_string();
+        }
+    };
+    // Podman identifies itself as a component (Podman Engine) of its compatible API
+    let podman = version
+        .components
+        .as_ref()
+        .is_some_and(|c| c.iter().any(|c| c.name.to_lowercase().contains("podman")))
+        || version
+            .platform
+            .as_ref()
+            .is_some_and(|p| p.name.to_lowercase().contains("podman"));
+    let detected = match podman {
+        true => "podman",
+        false => "docker",
+    };
+    let msg0 = format!(
+        "Detected {} engine {}",
+        detected,
+        version.version.unwrap_or("unknown".to_string())
+    );
+    log_message(&msg0, INFO).await;
+    match runtime {
+        "auto" => detected.to_string(),
+        r => {
+            if r != detected {
+                let msg1 = format!("Runtime {} requested, however {} was detected", r, detected);
+                log_message(&msg1, WARNING).await;
+            }
+            r.to_string()
+        }
+    }
+}
+
+// Ensure ssl files exist and hold the expected PEM content
+fn check_tls_files(key_path: &str, cert_path: &str, ca_path: &str) -> Result<(), String> {
+    let open = |path: &str, kind: &str| match std::fs::File::open(path) {
+        Ok(f) => Ok(BufReader::new(f)),
+        Err(e) => Err(format!("Unable to read {} ({}): {}", kind, path, e)),
+    };
+    match rustls_pemfile::private_key(&mut open(key_path, "private key")?) {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(format!("No private key found in {}", key_path)),
+        Err(e) => return Err(format!("Unable to parse private key ({}): {}", key_path, e)),
+    }
+    for (path, kind) in [(cert_path, "certificate"), (ca_path, "CA certificate")] {
+        let certs: Result<Vec<_>, _> = rustls_pemfile::certs(&mut open(path, kind)?).collect();
+        match certs {
+            Ok(c) if !c.is_empty() => (),
+            Ok(_) => return Err(format!("No {} found in {}", kind, path)),
+            Err(e) => return Err(format!("Unable to parse {} ({}): {}", kind, path, e)),
+        }
+    }
+    Ok(())
+}
+
+pub async fn connect_docker(var: ConnectionVariablesList) -> Docker {
+    // Prepare reusable objects
+    let connection_type = var.connection_type;
+    let tcp_address = var.tcp_address;
+    let tcp_timeout = var.tcp_timeout;
+    let key_path = var.key_path;
+    let cert_path = var.cert_path;
+    let ca_path = var.ca_path;
+    let socket_path = var.socket_path;
+    let ssh_host = var.ssh_host;
+    let ssh_key = var.ssh_key;
+    let ssh_known_hosts = var.ssh_known_hosts;
 
-pub async fn connect_docker(
-    connection_type: String,
-    tcp_address: String,
-    tcp_timeout: u64,
-    key_path: String,
-    cert_path: String,
-    ca_path: String,
-) -> Docker {
     // Log final connection parameters
     let msg0 = format!("Monitoring Docker via {}", connection_type);
     log_message(&msg0, INFO).await;
@@ -20,6 +124,18 @@ pub async fn connect_docker(
             log_message(&msg1, INFO).await;
             Docker::connect_with_http(&tcp_address, tcp_timeout, API_DEFAULT_VERSION)
         }
+        "socket" | "local" if !socket_path.is_empty() => {
+            let msg1 = format!("Connecting to {}", socket_path);
+            log_message(&msg1, INFO).await;
+            // Sockets on Windows are named pipes, e.g. //./pipe/docker_engine
+            #[cfg(windows)]
+            let docker =
+                Docker::connect_with_named_pipe(&socket_path, tcp_timeout, API_DEFAULT_VERSION);
+            #[cfg(not(windows))]
+            let docker =
+                Docker::connect_with_socket(&socket_path, tcp_timeout, API_DEFAULT_VERSION);
+            docker
+        }
         "socket" => Docker::connect_with_socket_defaults(),
         "ssl" => {
             let msg1 = format!("Connecting to {}", tcp_address);
@@ -29,6 +145,11 @@ pub async fn connect_docker(
                 key_path, cert_path, ca_path
             );
             log_message(&msg2, INFO).await;
+            if let Err(e) = check_tls_files(&key_path, &cert_path, &ca_path) {
+                let msg0 = format!("Invalid ssl configuration: {}", e);
+                log_message(&msg0, ERROR).await;
+                panic!("{e}")
+            }
             Docker::connect_with_ssl(
                 &tcp_address,
                 std::path::Path::new(&key_path),
@@ -38,6 +159,20 @@ pub async fn connect_docker(
                 API_DEFAULT_VERSION,
             )
         }
+        "ssh" => {
+            let msg1 = format!("Connecting to {}", ssh_host);
+            log_message(&msg1, INFO).await;
+            match open_tunnel(ssh_host, ssh_key, ssh_known_hosts, tcp_timeout).await {
+                Ok(socket) => {
+                    Docker::connect_with_socket(&socket, tcp_timeout, API_DEFAULT_VERSION)
+                }
+                Err(e) => {
+                    let msg0 = String::from("Could not reliably establish ssh tunnel");
+                    log_message(&msg0, ERROR).await;
+                    panic!("{e}")
+                }
+            }
+        }
         &_ => Docker::connect_with_local_defaults(),
     };
 
@@ -0,0 +1,249 @@
+use crate::{
+    execute::{action::execute_tasks, looper::TaskVariablesList},
+    inquire::{
+        compose::{restart_order, ComposeMemberList},
+        depends::get_dependants,
+        events::{get_deploy, is_stopping, EventHistoryList},
+        identity::get_identity,
+        inspect,
+        schedule::is_scheduled,
+    },
+    log_message,
+    report::{
+        logging::{log_read, log_write},
+        record::JsonRecord,
+    },
+    LoopVariablesList, DEBUG, WARNING,
+};
+use bollard::Docker;
+use chrono::{DateTime, FixedOffset};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+// Determine when a container started, where it is running and, with a healthcheck, healthy
+async fn get_started(
+    docker: &Docker,
+    name: &str,
+    recovered: bool,
+) -> Option<DateTime<FixedOffset>> {
+    let inspect = docker.inspect_container(name, None).await.ok()?;
+    let state = inspect.state.unwrap_or_default();
+    let health = state
+        .health
+        .and_then(|h| h.status)
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+    if recovered
+        && !(state.running == Some(true) && matches!(health.as_str(), "" | "none" | "healthy"))
+    {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(&state.started_at?).ok()
+}
+
+// Determine why a dependant must be left alone, as its own remediation would be
+async fn get_suppression(
+    var: &LoopVariablesList,
+    events: &Mutex<EventHistoryList>,
+    member: &ComposeMemberList,
+    labels: &HashMap<String, String>,
+    image: &str,
+) -> Option<String> {
+    let project = labels
+        .get("com.docker.compose.project")
+        .cloned()
+        .unwrap_or_default();
+    if is_stopping(events, &project) {
+        return Some(format!("compose project {} is going down", project));
+    }
+    let s = "autoheal.deploy.window".to_string();
+    let autoheal_deploy_window = match labels.get(&s) {
+        Some(v) => v.parse().unwrap_or(var.deploy_window),
+        None => var.deploy_window,
+    };
+    if let Some(d) = get_deploy(
+        events,
+        &member.name,
+        &project,
+        image,
+        autoheal_deploy_window,
+    ) {
+        return Some(format!("deploy activity was detected ({})", d));
+    }
+    let s = "autoheal.maintenance".to_string();
+    let autoheal_maintenance = match labels.get(&s) {
+        Some(v) => v.clone(),
+        None => var.maintenance.clone(),
+    };
+    match is_scheduled(&autoheal_maintenance, &member.name, &member.id).await {
+        true => Some("a maintenance window is open".to_string()),
+        false => None,
+    }
+}
+
+// Restart a dependant the way an unhealthy container is remediated, so it is recorded and notified
+async fn restart_dependant(
+    docker: &Docker,
+    var: &LoopVariablesList,
+    hostname: &str,
+    dependency: &str,
+    member: &ComposeMemberList,
+    labels: &HashMap<String, String>,
+) {
+    let name = member.name.clone();
+    let id = member.id.clone();
+    let s = "autoheal.stop.timeout".to_string();
+    let autoheal_stop_timeout = match labels.get(&s) {
+        Some(v) => v.parse().unwrap_or(var.stop_timeout),
+        None => var.stop_timeout,
+    };
+    let s = "autoheal.quiet.hours".to_string();
+    let autoheal_quiet_hours = match labels.get(&s) {
+        Some(v) => v.clone(),
+        None => var.quiet_hours.clone(),
+    };
+    let quiet = is_scheduled(&autoheal_quiet_hours, &name, &id).await;
+    let inspection = inspect::Result::triggered(format!("Dependency {} recovered", dependency), 1);
+    let exit_code = inspection.exit_code;
+    let fail_reason = inspection.failing_reason.clone();
+
+    // Remediate
+    let task_variables = TaskVariablesList {
+        hostname: hostname.to_string(),
+        docker: docker.clone(),
+        name: name.clone(),
+        id: id.clone(),
+        inspection,
+        stop_timeout: autoheal_stop_timeout,
+        apprise_url: var.apprise_url.clone(),
+        webhook_key: var.webhook_key.clone(),
+        webhook_url: var.webhook_url.clone(),
+        post_action: var.post_action.clone(),
+        restart_enable: true,
+        pod: "".to_string(),
+        service_id: "".to_string(),
+        service_name: "".to_string(),
+        swarm_action: var.swarm_action.clone(),
+        restart_scope: "container".to_string(),
+        project: "".to_string(),
+        service: "".to_string(),
+        action: "restart".to_string(),
+        rule: "".to_string(),
+        log_tail: vec![],
+        diagnostics: "".to_string(),
+        quiet,
+    };
+    let (msg, outcome) = execute_tasks(task_variables).await;
+
+    if var.log_ready {
+        let identity = get_identity(&var.identity_type, labels, &name, &id);
+        let data = JsonRecord {
+            date: chrono::Local::now()
+                .format("%Y-%m-%d %H:%M:%S%z")
+                .to_string(),
+            name: name.clone(),
+            id: id.clone(),
+            identity: identity.clone(),
+            host: hostname.to_string(),
+            code: exit_code,
+            err: fail_reason,
+            action: msg,
+            health: vec![],
+            logs: vec![],
+            incident: "".to_string(),
+            diagnostics: "".to_string(),
+            outcome: outcome.to_string(),
+        };
+        log_write(&var.log_path, data).await;
+        log_read(
+            &var.log_path,
+            &name,
+            id,
+            &identity,
+            hostname,
+            var.log_quarantine,
+        )
+        .await;
+    }
+}
+
+// Restart the deferred dependants of recovered dependencies, dependencies first
+pub async fn restart_dependants(
+    docker: &Docker,
+    var: &LoopVariablesList,
+    hostname: &str,
+    events: &Mutex<EventHistoryList>,
+    recovered: Vec<String>,
+    deferred: &HashSet<String>,
+) -> HashSet<String> {
+    let mut restarted = HashSet::new();
+    let dependants = get_dependants(docker, var.monitor_all).await;
+    for dependency in recovered {
+        if !dependants
+            .iter()
+            .any(|d| d.depends_on.contains(&dependency))
+        {
+            continue;
+        }
+        let Some(recovered_at) = get_started(docker, &dependency, true).await else {
+            continue;
+        };
+        // Treat the dependency as a member so its (transitive) dependants are selected in order
+        let mut members: Vec<ComposeMemberList> = dependants.clone();
+        members.push(ComposeMemberList {
+            name: dependency.clone(),
+            id: "".to_string(),
+            service: dependency.clone(),
+            depends_on: vec![],
+        });
+        // Only containers whose remediation was deferred, as the others are healthy
+        let mut ordered: Vec<(ComposeMemberList, HashMap<String, String>)> = vec![];
+        for member in restart_order(members, &dependency, "dependents") {
+            if member.name == dependency
+                || restarted.contains(&member.name)
+                || !deferred.contains(&member.name)
+            {
+                continue;
+            }
+            // Already restarted along with the dependency, e.g. by restart scope dependents
+            if get_started(docker, &member.id, false)
+                .await
+                .is_some_and(|s| s >= recovered_at)
+            {
+                continue;
+            }
+            let Ok(inspect) = docker.inspect_container(&member.id, None).await else {
+                continue;
+            };
+            let config = inspect.config.unwrap_or_default();
+            let labels = config.labels.unwrap_or_default();
+            let image = config.image.unwrap_or_default();
+            // Left to the unhealthy container path, which reports the suppression once
+            if let Some(reason) = get_suppression(var, events, &member, &labels, &image).await {
+                let msg0 = format!(
+                    "[{} ({})] Container restart after {} recovered is skipped as {}",
+                    member.name, member.id, dependency, reason
+                );
+                log_message(&msg0, DEBUG).await;
+                continue;
+            }
+            ordered.push((member, labels));
+        }
+        if ordered.is_empty() {
+            continue;
+        }
+        let msg0 = format!(
+            "[{}] Dependency recovered; restarting {} dependants",
+            dependency,
+            ordered.len()
+        );
+        log_message(&msg0, WARNING).await;
+        for (member, labels) in ordered {
+            restart_dependant(docker, var, hostname, &dependency, &member, &labels).await;
+            restarted.insert(member.name);
+        }
+    }
+    restarted
+}
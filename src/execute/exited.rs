@@ -0,0 +1,292 @@
+use crate::{
+    inquire::{
+        events::{get_deploy, has_died, is_stopped, is_stopping, EventHistoryList},
+        identity::get_identity,
+        schedule::is_scheduled,
+    },
+    report::{
+        logging::{log_message, log_read, log_write},
+        record::JsonRecord,
+        webhook::notify_webhook,
+    },
+    LoopVariablesList, ERROR, INFO, WARNING,
+};
+use bollard::{
+    container::{ListContainersOptions, RestartContainerOptions},
+    models::RestartPolicyNameEnum,
+    Docker,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+// Window over which the restart budget of an exited container applies
+const EXITED_BUDGET_WINDOW: Duration = Duration::from_secs(3600);
+
+// When exited containers were first looked for, as those that exited before are not ours to revive
+static STARTED: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+// Restart attempts per container id, and containers whose budget exhaustion or deploy
+// suppression was reported
+#[derive(Default)]
+pub struct ExitedHistoryList {
+    attempts: HashMap<String, Vec<Instant>>,
+    exhausted: HashSet<String>,
+    suppressed: HashSet<String>,
+}
+
+async fn notify(var: &LoopVariablesList, hostname: &str, msg1: &str, msg: &str) {
+    // Send webhook
+    if !(var.webhook_url.is_empty() || var.webhook_key.is_empty()) {
+        let payload = json!({
+            &var.webhook_key: format!("{}|{}|{}", hostname, msg1, msg)
+        })
+        .to_string();
+        notify_webhook(&var.webhook_url, &payload).await;
+    }
+    // Send apprise
+    if !var.apprise_url.is_empty() {
+        let payload = json!({
+            "title": "Docker-Autoheal",
+            "body": format!("{}|{}|{}", hostname, msg1, msg)
+        })
+        .to_string();
+        notify_webhook(&var.apprise_url, &payload).await;
+    }
+}
+
+// Restart containers that crashed, exited with a non-zero code or were OOM-killed
+pub async fn remediate_exited(
+    docker: &Docker,
+    var: &LoopVariablesList,
+    hostname: &str,
+    runtime: &str,
+    history: &mut ExitedHistoryList,
+    events: &Mutex<EventHistoryList>,
+) {
+    let started = *STARTED.get_or_init(Utc::now);
+    let mut filters = HashMap::new();
+    match runtime {
+        "podman" => filters.insert("status", vec!["exited"]),
+        _ => filters.insert("status", vec!["exited", "dead"]),
+    };
+    let container_options = Some(ListContainersOptions {
+        all: true,
+        filters,
+        ..Default::default()
+    });
+    let containers = match docker.list_containers(container_options).await {
+        Ok(list) => list,
+        Err(e) => {
+            let msg0 = format!("Could not reliably determine exited containers: {}", e);
+            log_message(&msg0, ERROR).await;
+            return;
+        }
+    };
+    let mut seen = HashSet::new();
+    for container in containers {
+        let labels = container.labels.clone().unwrap_or_default();
+        let name = container
+            .names
+            .as_ref()
+            .and_then(|n| n.first())
+            .map(|n| n.trim_matches('/').to_string())
+            .unwrap_or_default();
+        let id: String = container.id.unwrap_or_default().chars().take(12).collect();
+        if id.is_empty() {
+            continue;
+        }
+
+        // Determine if opted in
+        let s = "autoheal.exited.enable".to_string();
+        let autoheal_exited_enable = match labels.get(&s) {
+            Some(v) => v.parse().unwrap_or(var.monitor_exited),
+            None => var.monitor_exited,
+        };
+        let s = "autoheal.restart.enable".to_string();
+        let autoheal_restart_enable = match labels.get(&s) {
+            Some(v) => v.parse().unwrap_or(true),
+            None => true,
+        };
+        if !autoheal_exited_enable || !autoheal_restart_enable {
+            continue;
+        }
+        // Never resurrect a container stopped on request, or race a compose project going down
+        let project = labels
+            .get("com.docker.compose.project")
+            .cloned()
+            .unwrap_or_default();
+        if is_stopped(events, &id) || is_stopping(events, &project) {
+            continue;
+        }
+        let s = "autoheal.exited.budget".to_string();
+        let autoheal_exited_budget = match labels.get(&s) {
+            Some(v) => v.parse().unwrap_or(var.exited_budget),
+            None => var.exited_budget,
+        };
+        let s = "autoheal.stop.timeout".to_string();
+        let autoheal_stop_timeout = match labels.get(&s) {
+            Some(v) => v.parse().unwrap_or(var.stop_timeout),
+            None => var.stop_timeout,
+        };
+
+        let Ok(inspect) = docker.inspect_container(&id, None).await else {
+            continue;
+        };
+        // Leave containers with a restart policy to Docker
+        let policy = inspect
+            .host_config
+            .and_then(|h| h.restart_policy)
+            .and_then(|r| r.name)
+            .unwrap_or(RestartPolicyNameEnum::EMPTY);
+        if !matches!(
+            policy,
+            RestartPolicyNameEnum::EMPTY | RestartPolicyNameEnum::NO
+        ) {
+            continue;
+        }
+        let state = inspect.state.unwrap_or_default();
+        let exit_code = state.exit_code.unwrap_or(-1);
+        let oom_killed = state.oom_killed.unwrap_or(false);
+        // A clean exit is expected; stops on request are known from the events stream
+        if !oom_killed && exit_code == 0 {
+            continue;
+        }
+        // Only containers that exited while monitored, allowing for the clock of a remote host
+        let finished = state
+            .finished_at
+            .and_then(|f| DateTime::parse_from_rfc3339(&f).ok())
+            .is_some_and(|f| f > started);
+        if !finished && !has_died(events, &id) {
+            continue;
+        }
+        seen.insert(id.clone());
+
+        // Leave containers being deployed to settle, and honour maintenance windows
+        let s = "autoheal.deploy.window".to_string();
+        let autoheal_deploy_window = match labels.get(&s) {
+            Some(v) => v.parse().unwrap_or(var.deploy_window),
+            None => var.deploy_window,
+        };
+        let s = "autoheal.maintenance".to_string();
+        let autoheal_maintenance = match labels.get(&s) {
+            Some(v) => v.clone(),
+            None => var.maintenance.clone(),
+        };
+        let s = "autoheal.quiet.hours".to_string();
+        let autoheal_quiet_hours = match labels.get(&s) {
+            Some(v) => v.clone(),
+            None => var.quiet_hours.clone(),
+        };
+        let image = container.image.clone().unwrap_or_default();
+        let suppression = match get_deploy(events, &name, &project, &image, autoheal_deploy_window)
+        {
+            Some(d) => format!("deploy activity was detected ({})", d),
+            None if is_scheduled(&autoheal_maintenance, &name, &id).await => {
+                "a maintenance window is open".to_string()
+            }
+            None => "".to_string(),
+        };
+        if !suppression.is_empty() {
+            if history.suppressed.insert(id.clone()) {
+                let msg0 = format!(
+                    "[{} ({})] Container exited, however remediation is suppressed as {}",
+                    name, id, suppression
+                );
+                log_message(&msg0, INFO).await;
+            }
+            continue;
+        }
+
+        // Apply the restart budget
+        let attempts = history.attempts.entry(id.clone()).or_default();
+        attempts.retain(|a| a.elapsed() < EXITED_BUDGET_WINDOW);
+        if autoheal_exited_budget > 0 && attempts.len() as u64 >= autoheal_exited_budget {
+            if history.exhausted.insert(id.clone()) {
+                let msg0 = format!(
+                    "[{} ({})] Container restart budget of {} per hour is exhausted; no further restarts",
+                    name, id, autoheal_exited_budget
+                );
+                log_message(&msg0, ERROR).await;
+                notify(var, hostname, &msg0, "Restart budget exhausted").await;
+            }
+            continue;
+        }
+        attempts.push(Instant::now());
+        history.exhausted.remove(&id);
+
+        // Report exited container
+        let reason = match oom_killed {
+            true => "OOM killed".to_string(),
+            false => format!("exited with code {}", exit_code),
+        };
+        let msg1 = format!("[{} ({})] Container {} unexpectedly", name, id, reason);
+        log_message(&msg1, WARNING).await;
+
+        let msg0 = format!(
+            "[{} ({})] Container restarting with {}s timeout",
+            name, id, autoheal_stop_timeout
+        );
+        log_message(&msg0, WARNING).await;
+        let restart_options = Some(RestartContainerOptions {
+            t: autoheal_stop_timeout,
+        });
+        let (msg, outcome) = match docker.restart_container(&id, restart_options).await {
+            Ok(()) => {
+                let msg0 = format!("[{} ({})] Container restart was successful", name, id);
+                log_message(&msg0, INFO).await;
+                (msg0, "success")
+            }
+            Err(e) => {
+                let msg0 = format!("[{} ({})] Container restart failed: {}", name, id, e);
+                log_message(&msg0, ERROR).await;
+                (msg0, "failed")
+            }
+        };
+        // Only failed remediation is notified during quiet hours
+        if outcome == "failed" || !is_scheduled(&autoheal_quiet_hours, &name, &id).await {
+            notify(var, hostname, &msg1, &msg).await;
+        }
+
+        if var.log_ready {
+            let identity = get_identity(&var.identity_type, &labels, &name, &id);
+            let data = JsonRecord {
+                date: chrono::Local::now()
+                    .format("%Y-%m-%d %H:%M:%S%z")
+                    .to_string(),
+                name: name.clone(),
+                id: id.clone(),
+                identity: identity.clone(),
+                host: hostname.to_string(),
+                code: exit_code,
+                err: reason,
+                action: msg,
+                health: vec![],
+                logs: vec![],
+                incident: "".to_string(),
+                diagnostics: "".to_string(),
+                outcome: outcome.to_string(),
+            };
+            log_write(&var.log_path, data).await;
+            log_read(
+                &var.log_path,
+                &name,
+                id.clone(),
+                &identity,
+                hostname,
+                var.log_quarantine,
+            )
+            .await;
+        }
+    }
+    // Forget containers that are no longer exited, once their budget window has passed
+    history.attempts.retain(|id, attempts| {
+        seen.contains(id) || attempts.iter().any(|a| a.elapsed() < EXITED_BUDGET_WINDOW)
+    });
+    history.exhausted.retain(|id| seen.contains(id));
+    history.suppressed.retain(|id| seen.contains(id));
+}
@@ -1,17 +1,40 @@
 use crate::{
-    execute::action::execute_tasks,
+    execute::{
+        action::execute_tasks,
+        connect::{connect_docker, detect_runtime, is_remote},
+        dependants::restart_dependants,
+        exited::{remediate_exited, ExitedHistoryList},
+    },
     inquire::{
-        inspect::{self, inspect_container},
+        depends::get_depends_on,
+        endpoints::EndpointList,
+        events::{get_deploy, is_stopping, start_events},
+        identity::get_identity,
+        inspect::{self, has_no_restart_policy, inspect_container},
         list::containers_list,
+        logs::get_log_tail,
+        patterns::{start_patterns, take_matched, PatternDefaultsList},
+        pod::get_pod,
+        probe::{run_probes, ProbeHistoryList},
+        rules::get_action,
+        schedule::is_scheduled,
+        service::get_service,
+        stats::{start_stats, take_triggered},
     },
     report::{
-        logging::{log_message, log_read, log_write},
+        logging::{
+            log_diagnostics, log_incident, log_message, log_read, log_rotate, log_write, ENDPOINT,
+        },
         record::JsonRecord,
     },
-    LoopVariablesList, ERROR, WARNING,
+    LoopVariablesList, ALLOWED_POD_RESTARTS, ALLOWED_RESTART_SCOPES, ALLOWED_SWARM_ACTIONS, DEBUG,
+    ERROR, INFO, WARNING,
+};
+use bollard::{models::ContainerSummary, Docker};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
 };
-use bollard::Docker;
-use std::time::Duration;
 
 pub struct TaskVariablesList {
     pub hostname: String,
@@ -25,6 +48,18 @@ pub struct TaskVariablesList {
     pub webhook_url: String,
     pub post_action: String,
     pub restart_enable: bool,
+    pub pod: String,
+    pub service_id: String,
+    pub service_name: String,
+    pub swarm_action: String,
+    pub restart_scope: String,
+    pub project: String,
+    pub service: String,
+    pub action: String,
+    pub rule: String,
+    pub log_tail: Vec<String>,
+    pub diagnostics: String,
+    pub quiet: bool,
 }
 
 pub async fn start_loop(
@@ -34,18 +69,99 @@ pub async fn start_loop(
     // Get System Information
     let sys_info = docker.info().await;
     let hostname = sys_info.unwrap().name.unwrap_or("unknown".to_string());
+    let runtime = detect_runtime(&docker, &var.runtime).await;
+
+    // Unhealthy containers of the previous cycle, to notice dependencies recovering,
+    // and containers whose remediation is deferred on an unhealthy dependency
+    let mut previous: HashSet<String> = HashSet::new();
+    let mut deferred: HashSet<String> = HashSet::new();
+    // Containers whose remediation is skipped, so it is reported once per episode
+    let mut suppressed: HashSet<String> = HashSet::new();
+    // Containers stopped on request and compose projects going down, from the events stream
+    let events = start_events(docker.clone(), var.interval);
+    // Restart attempts of exited containers against their budget
+    let mut exited = ExitedHistoryList::default();
+    // Results of external probes for containers without a healthcheck
+    let mut probes = ProbeHistoryList::default();
+    // Containers exceeding resource thresholds, sampled in the background
+    let stats = start_stats(docker.clone(), var.interval);
+    // Containers whose log matches a pattern, followed in the background
+    let patterns = start_patterns(
+        docker.clone(),
+        PatternDefaultsList {
+            pattern: var.log_pattern.clone(),
+            threshold: var.log_pattern_threshold,
+            window: var.log_pattern_window,
+            monitor_all: var.monitor_all,
+        },
+        var.interval,
+    );
+    // When each container was first seen unhealthy
+    let mut unhealthy_since: HashMap<String, Instant> = HashMap::new();
 
     // Establish loop interval
     let mut interval = tokio::time::interval(Duration::from_secs(var.interval));
     loop {
+        // Apply retention to persistent log ahead of this cycle's writes
+        if var.log_ready {
+            log_rotate(&var.log_path, &var.retention).await;
+        }
+        // Restart containers that exited unexpectedly, where opted in
+        remediate_exited(&docker, &var, &hostname, &runtime, &mut exited, &events).await;
         // Gather all unhealthy containers
-        let containers = containers_list(docker.clone()).await;
+        let mut containers: Vec<(ContainerSummary, Option<inspect::Result>)> =
+            containers_list(docker.clone(), &runtime)
+                .await
+                .into_iter()
+                .map(|c| (c, None))
+                .collect();
+        // Containers failing their external probes follow the same remediation path
+        let failed = run_probes(&docker, &mut probes, var.interval, var.remote).await;
+        for (container, result) in failed {
+            if !containers.iter().any(|(c, _)| c.id == container.id) {
+                containers.push((container, Some(result)));
+            }
+        }
+        // As do containers that exceeded a resource threshold for long enough
+        for (container, result) in take_triggered(&stats) {
+            if !containers.iter().any(|(c, _)| c.id == container.id) {
+                containers.push((container, Some(result)));
+            }
+        }
+        // And containers whose log matched their pattern often enough
+        for (container, result) in take_matched(&patterns) {
+            if !containers.iter().any(|(c, _)| c.id == container.id) {
+                containers.push((container, Some(result)));
+            }
+        }
+        // Global view of this cycle's unhealthy containers so dependants can wait on dependencies
+        let unhealthy: HashSet<String> = containers
+            .iter()
+            .filter_map(|(c, _)| Some(c.names.as_ref()?.first()?.trim_matches('/').to_string()))
+            .collect();
+        // Restart the dependants of dependencies that recovered since the last cycle
+        let recovered: Vec<String> = previous.difference(&unhealthy).cloned().collect();
+        let restarted = match recovered.is_empty() {
+            true => HashSet::new(),
+            false => {
+                restart_dependants(&docker, &var, &hostname, &events, recovered, &deferred).await
+            }
+        };
+        deferred.retain(|n| unhealthy.contains(n));
+        suppressed.retain(|n| unhealthy.contains(n));
+        unhealthy_since.retain(|id, _| {
+            containers
+                .iter()
+                .any(|(c, _)| c.id.as_ref().is_some_and(|i| i == id))
+        });
+        previous.clone_from(&unhealthy);
         // Prepare for concurrent execution
         let mut handles = vec![];
         // Iterate through suspected unhealthy
-        for container in containers {
+        for (container, probe) in containers {
             // Prepare reusable objects
             let hostname_clone = hostname.clone();
+            let host = hostname.clone();
             let docker_clone = docker.clone();
             let apprise_url = var.apprise_url.clone();
             let webhook_key = var.webhook_key.clone();
@@ -54,40 +170,208 @@ pub async fn start_loop(
             let log_all = var.log_all;
             let monitor_all = var.monitor_all;
             let log_ready = var.log_ready;
+            let log_quarantine = var.log_quarantine;
+            let log_path = var.log_path.clone();
+            let identity_type = var.identity_type.clone();
+            let runtime = runtime.clone();
+            let ignore_output = var.ignore_output.clone();
+            let unhealthy_for = unhealthy_since
+                .entry(container.id.clone().unwrap_or_default())
+                .or_insert_with(Instant::now)
+                .elapsed()
+                .as_secs();
+            let mut labels = container.labels.clone().unwrap_or_default();
+            let container_name = container
+                .names
+                .as_ref()
+                .and_then(|n| n.first())
+                .map(|n| n.trim_matches('/').to_string())
+                .unwrap_or_default();
+            // Already restarted this cycle as the dependant of a recovered dependency
+            if restarted.contains(&container_name) {
+                continue;
+            }
             let mut msg: String = "".to_string();
+            let mut outcome = "other";
             let mut fail_reason: String = "".to_string();
             let mut exit_code: i64 = -99;
+            let mut health = vec![];
+            let mut logs: Vec<String> = vec![];
+            let mut diagnostics = String::new();
+
+            // Swarm task containers inherit autoheal labels set on their service
+            let service = get_service(&docker, &labels).await;
+            if let Some(ref s) = service {
+                for (k, v) in &s.labels {
+                    labels.entry(k.clone()).or_insert(v.clone());
+                }
+            }
 
             // Determine if stop override label
             let s = "autoheal.stop.timeout".to_string();
-            let autoheal_stop_timeout = match container.labels {
-                Some(ref label) => match label.get(&s) {
-                    Some(v) => v.parse().unwrap_or(var.stop_timeout),
-                    None => var.stop_timeout,
-                },
+            let autoheal_stop_timeout = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.stop_timeout),
                 None => var.stop_timeout,
             };
 
             // Determine if excluded
             let s = "autoheal.monitor.enable".to_string();
-            let autoheal_monitor_enable = match container.labels {
-                Some(ref label) => match label.get(&s) {
-                    Some(v) => v.parse().unwrap_or(monitor_all),
-                    None => monitor_all,
-                },
-                None => monitor_all,
+            // Declaring a probe opts the container in to monitoring
+            let autoheal_monitor_enable = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(monitor_all || probe.is_some()),
+                None => monitor_all || probe.is_some(),
             };
             let s = "autoheal.restart.enable".to_string();
-            let autoheal_restart_enable = match container.labels {
-                Some(ref label) => match label.get(&s) {
-                    Some(v) => v.parse().unwrap_or(true),
-                    None => true,
-                },
+            let autoheal_restart_enable = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(true),
                 None => true,
             };
 
+            // Determine how to remediate a Swarm task
+            let s = "autoheal.swarm.action".to_string();
+            let autoheal_swarm_action = match labels.get(&s) {
+                Some(v) if ALLOWED_SWARM_ACTIONS.contains(&v.as_str()) => v.clone(),
+                _ => var.swarm_action.clone(),
+            };
+
+            // Determine whether to restart compose dependants or the whole project
+            let s = "autoheal.restart.scope".to_string();
+            let autoheal_restart_scope = match labels.get(&s) {
+                Some(v) if ALLOWED_RESTART_SCOPES.contains(&v.as_str()) => v.clone(),
+                _ => var.restart_scope.clone(),
+            };
+            let project = labels
+                .get("com.docker.compose.project")
+                .cloned()
+                .unwrap_or_default();
+            let compose_service = labels
+                .get("com.docker.compose.service")
+                .cloned()
+                .unwrap_or_default();
+            // Determine dependencies that are themselves unhealthy
+            let blocked: Vec<String> = get_depends_on(&labels)
+                .into_iter()
+                .filter(|d| d != &container_name && unhealthy.contains(d))
+                .collect();
+            // Report deferral once per episode rather than every cycle
+            let first_deferral = match blocked.is_empty() {
+                true => false,
+                false => deferred.insert(container_name.clone()),
+            };
+            // Determine how long to leave containers to settle after deploy activity
+            let s = "autoheal.deploy.window".to_string();
+            let autoheal_deploy_window = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.deploy_window),
+                None => var.deploy_window,
+            };
+            let deploy = get_deploy(
+                &events,
+                &container_name,
+                &project,
+                container.image.as_deref().unwrap_or(""),
+                autoheal_deploy_window,
+            );
+
+            // Determine maintenance windows and quiet hours
+            let s = "autoheal.maintenance".to_string();
+            let autoheal_maintenance = match labels.get(&s) {
+                Some(v) => v.clone(),
+                None => var.maintenance.clone(),
+            };
+            let s = "autoheal.quiet.hours".to_string();
+            let autoheal_quiet_hours = match labels.get(&s) {
+                Some(v) => v.clone(),
+                None => var.quiet_hours.clone(),
+            };
+            let id_short: String = container
+                .id
+                .as_deref()
+                .unwrap_or("")
+                .chars()
+                .take(12)
+                .collect();
+            let quiet = is_scheduled(&autoheal_quiet_hours, &container_name, &id_short).await;
+
+            // Never race a compose project going down or a deploy, and honour maintenance windows
+            // and restart policy no where requested
+            let suppression = if is_stopping(&events, &project) {
+                format!("compose project {} is going down", project)
+            } else if let Some(d) = deploy {
+                format!("deploy activity was detected ({})", d)
+            } else if autoheal_monitor_enable
+                && is_scheduled(&autoheal_maintenance, &container_name, &id_short).await
+            {
+                "a maintenance window is open".to_string()
+            } else if autoheal_monitor_enable
+                && var.skip_restart_no
+                && has_no_restart_policy(&docker, container.id.as_deref().unwrap_or("")).await
+            {
+                "its restart policy is no".to_string()
+            } else {
+                "".to_string()
+            };
+            let first_suppression = match suppression.is_empty() {
+                true => false,
+                false => suppressed.insert(container_name.clone()),
+            };
+            let (service_id, service_name) = match service {
+                Some(s) => (s.id, s.name),
+                None => ("".to_string(), "".to_string()),
+            };
+
+            // Determine how long a container must be failing before acting
+            let s = "autoheal.failing.threshold".to_string();
+            let autoheal_failing_threshold = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.failing_threshold),
+                None => var.failing_threshold,
+            };
+            let s = "autoheal.unhealthy.duration".to_string();
+            let autoheal_unhealthy_duration = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.unhealthy_duration),
+                None => var.unhealthy_duration,
+            };
+
+            // Determine how much of the container log to capture before acting
+            let s = "autoheal.log.tail".to_string();
+            let autoheal_log_tail = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.log_tail),
+                None => var.log_tail,
+            };
+            let s = "autoheal.log.tail.bytes".to_string();
+            let autoheal_log_tail_bytes = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.log_tail_bytes),
+                None => var.log_tail_bytes,
+            };
+            let s = "autoheal.log.tail.notify".to_string();
+            let autoheal_log_tail_notify = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.log_tail_notify),
+                None => var.log_tail_notify,
+            };
+
+            // Determine whether to capture a diagnostics bundle before acting
+            let s = "autoheal.diagnostics.enable".to_string();
+            let autoheal_diagnostics = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.diagnostics),
+                None => var.diagnostics,
+            };
+            let s = "autoheal.diagnostics.export".to_string();
+            let autoheal_diagnostics_export = match labels.get(&s) {
+                Some(v) => v.parse().unwrap_or(var.diagnostics_export),
+                None => var.diagnostics_export,
+            };
+
+            // Determine whether to restart the whole Podman pod
+            let s = "autoheal.pod.restart".to_string();
+            let autoheal_pod_restart = match labels.get(&s) {
+                Some(v) if ALLOWED_POD_RESTARTS.contains(&v.as_str()) => v.clone(),
+                _ => var.pod_restart.clone(),
+            };
+
+            // Carry the monitored endpoint into each task for logging
+            let endpoint = ENDPOINT.try_with(|e| e.clone()).unwrap_or_default();
+
             // Execute concurrently
-            let handle = tokio::task::spawn(async move {
+            let handle = tokio::task::spawn(ENDPOINT.scope(endpoint, async move {
                 // Get name of container
                 let name_tmp = match &container.names {
                     Some(names) => &names[0],
@@ -109,6 +393,9 @@ pub async fn start_loop(
                     }
                 };
 
+                // Get stable identity of container for persistent log data
+                let identity = get_identity(&identity_type, &labels, name, &id);
+
                 // Have all tests passed for unhealthy container to be remediated
                 if name.is_empty() && id.is_empty() {
                     msg = format!(
@@ -122,12 +409,110 @@ pub async fn start_loop(
                         name, id
                     );
                     log_message(&msg, WARNING).await;
+                    outcome = "disabled";
+                } else if autoheal_monitor_enable && !blocked.is_empty() {
+                    if first_deferral {
+                        msg = format!(
+                            "[{} ({})] Container is unhealthy, however remediation is deferred until {} recovers",
+                            name,
+                            id,
+                            blocked.join(",")
+                        );
+                        log_message(&msg, WARNING).await;
+                    }
+                } else if autoheal_monitor_enable && !suppression.is_empty() {
+                    if first_suppression {
+                        msg = format!(
+                            "[{} ({})] Container is unhealthy, however remediation is skipped as {}",
+                            name, id, suppression
+                        );
+                        log_message(&msg, WARNING).await;
+                    }
                 } else if autoheal_monitor_enable && (autoheal_restart_enable || log_all) {
                     // Determine failing streak of the unhealthy container
-                    let inspection = inspect_container(docker_clone.clone(), name, &id).await;
+                    // Probes apply their own threshold
+                    let probed = probe.is_some();
+                    let inspection = match probe {
+                        Some(p) => p,
+                        None => inspect_container(docker_clone.clone(), name, &id, &runtime).await,
+                    };
                     fail_reason.clone_from(&inspection.failing_reason);
                     exit_code = inspection.exit_code;
-                    if inspection.failed {
+                    health.clone_from(&inspection.health_log);
+                    // Choose the action from healthcheck output and exit code rules
+                    let (action, rule) = match inspection.failed {
+                        true => {
+                            get_action(
+                                &labels,
+                                &ignore_output,
+                                &inspection.output,
+                                inspection.exit_code,
+                                name,
+                                &id,
+                            )
+                            .await
+                        }
+                        false => ("restart".to_string(), "".to_string()),
+                    };
+                    if inspection.failed
+                        && !probed
+                        && (inspection.failing_streak < autoheal_failing_threshold
+                            || unhealthy_for < autoheal_unhealthy_duration)
+                    {
+                        let msg0 = format!(
+                            "[{} ({})] Container is unhealthy with {} failures for {}s; waiting for {} failures for {}s",
+                            name,
+                            id,
+                            inspection.failing_streak,
+                            unhealthy_for,
+                            autoheal_failing_threshold,
+                            autoheal_unhealthy_duration
+                        );
+                        log_message(&msg0, DEBUG).await;
+                        fail_reason.clear();
+                    } else if inspection.failed && action == "ignore" {
+                        let msg0 = format!(
+                            "[{} ({})] Container is unhealthy, however remediation is skipped as {}",
+                            name, id, rule
+                        );
+                        log_message(&msg0, DEBUG).await;
+                        fail_reason.clear();
+                    } else if inspection.failed {
+                        // Resolve pod membership only when the pod is to be restarted
+                        let pod = match runtime == "podman" && autoheal_pod_restart == "pod" {
+                            true => {
+                                get_pod(&docker_clone, &labels, &inspection.hostname, &id).await
+                            }
+                            false => "".to_string(),
+                        };
+                        // Capture the container log while it still reflects the failure
+                        logs = get_log_tail(
+                            &docker_clone,
+                            &id,
+                            autoheal_log_tail,
+                            autoheal_log_tail_bytes,
+                        )
+                        .await;
+                        // Preserve the evidence a restart would destroy
+                        if autoheal_diagnostics && log_ready {
+                            let bundle = format!(
+                                "{}-{}-{}",
+                                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                                name,
+                                id
+                            );
+                            if log_diagnostics(
+                                &docker_clone,
+                                &log_path,
+                                &bundle,
+                                &id,
+                                autoheal_diagnostics_export,
+                            )
+                            .await
+                            {
+                                diagnostics = bundle;
+                            }
+                        }
                         // Remediate
                         let task_variables = {
                             TaskVariablesList {
@@ -142,30 +527,61 @@ pub async fn start_loop(
                                 webhook_url,
                                 post_action,
                                 restart_enable: autoheal_restart_enable,
+                                pod,
+                                service_id,
+                                service_name,
+                                swarm_action: autoheal_swarm_action,
+                                restart_scope: autoheal_restart_scope,
+                                project,
+                                service: compose_service,
+                                action,
+                                rule,
+                                log_tail: match autoheal_log_tail_notify {
+                                    true => logs.clone(),
+                                    false => vec![],
+                                },
+                                diagnostics: diagnostics.clone(),
+                                quiet,
                             }
                         };
-                        msg = execute_tasks(task_variables).await
+                        (msg, outcome) = execute_tasks(task_variables).await
                     }
                 }
 
                 if log_ready && !(msg.is_empty() && fail_reason.is_empty()) {
+                    let date = chrono::Local::now();
+                    // Save captured container log lines as a per-incident file
+                    let incident = match logs.is_empty() {
+                        true => "".to_string(),
+                        false => {
+                            let file_name =
+                                format!("{}-{}-{}.log", date.format("%Y%m%d-%H%M%S"), name, id);
+                            log_incident(&log_path, &file_name, &logs).await;
+                            file_name
+                        }
+                    };
                     // Write to log.json
                     let data: JsonRecord = {
                         JsonRecord {
-                            date: chrono::Local::now()
-                                .format("%Y-%m-%d %H:%M:%S%z")
-                                .to_string(),
+                            date: date.format("%Y-%m-%d %H:%M:%S%z").to_string(),
                             name: name.to_string(),
                             id: id.clone(),
+                            identity: identity.clone(),
+                            host: host.clone(),
                             code: exit_code,
                             err: fail_reason,
                             action: msg,
+                            health,
+                            logs,
+                            incident,
+                            diagnostics,
+                            outcome: outcome.to_string(),
                         }
                     };
-                    log_write(data).await;
-                    log_read(name, id).await;
+                    log_write(&log_path, data).await;
+                    log_read(&log_path, name, id, &identity, &host, log_quarantine).await;
                 }
-            });
+            }));
             // Push handles for later consumption
             handles.push(handle);
         }
@@ -177,3 +593,52 @@ pub async fn start_loop(
         interval.tick().await;
     }
 }
+
+// Monitor each Docker host concurrently, isolated so that one failing host does not stall the others
+pub async fn start_endpoints(
+    var: LoopVariablesList,
+    endpoints: Vec<EndpointList>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut handles = vec![];
+    for endpoint in endpoints {
+        let mut loop_variables = var.clone();
+        loop_variables.interval = endpoint.interval;
+        loop_variables.stop_timeout = endpoint.stop_timeout;
+        loop_variables.apprise_url = endpoint.apprise_url.clone();
+        loop_variables.webhook_key = endpoint.webhook_key.clone();
+        loop_variables.webhook_url = endpoint.webhook_url.clone();
+        loop_variables.post_action = endpoint.post_action.clone();
+        loop_variables.runtime = endpoint.runtime.clone();
+        loop_variables.remote = is_remote(&endpoint.connection);
+
+        let handle = tokio::task::spawn(ENDPOINT.scope(endpoint.name.clone(), async move {
+            loop {
+                let endpoint = endpoint.clone();
+                let loop_variables = loop_variables.clone();
+                // Supervise monitoring so a panic or error is contained to this endpoint
+                let monitor =
+                    tokio::task::spawn(ENDPOINT.scope(endpoint.name.clone(), async move {
+                        let docker = connect_docker(endpoint.connection).await;
+                        start_loop(loop_variables, docker)
+                            .await
+                            .map_err(|e| e.to_string())
+                    }));
+                let msg0 = match monitor.await {
+                    Ok(Ok(())) => String::from("Monitoring stopped"),
+                    Ok(Err(e)) => format!("Monitoring stopped: {}", e),
+                    Err(e) => format!("Monitoring failed: {}", e),
+                };
+                log_message(&msg0, ERROR).await;
+                let msg1 = format!("Reconnecting in {}s", endpoint.interval);
+                log_message(&msg1, INFO).await;
+                tokio::time::sleep(Duration::from_secs(endpoint.interval)).await;
+            }
+        }));
+        handles.push(handle);
+    }
+    // Return JoinHandle results as they arrive
+    for join in handles {
+        join.await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use bollard::{
+    container::RemoveContainerOptions, errors::Error, service::UpdateServiceOptions, Docker,
+};
+
+// Equivalent of `docker service update --force`, rescheduling every task of the service
+pub async fn force_update(docker: &Docker, service_id: &str) -> Result<(), Error> {
+    let service = docker.inspect_service(service_id, None).await?;
+    let version = service.version.and_then(|v| v.index).unwrap_or_default();
+    let mut spec = service.spec.unwrap_or_default();
+    let mut task_template = spec.task_template.unwrap_or_default();
+    task_template.force_update = Some(task_template.force_update.unwrap_or(0) + 1);
+    spec.task_template = Some(task_template);
+    let options = UpdateServiceOptions {
+        version,
+        ..Default::default()
+    };
+    docker
+        .update_service(service_id, spec, options, None)
+        .await
+        .map(|_| ())
+}
+
+// Remove the task container so that Swarm reschedules it
+pub async fn remove_task(docker: &Docker, id: &str) -> Result<(), Error> {
+    let options = Some(RemoveContainerOptions {
+        force: true,
+        ..Default::default()
+    });
+    docker.remove_container(id, options).await
+}
@@ -0,0 +1,292 @@
+use crate::{log_message, ERROR, INFO, WARNING};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+use tokio::process::Command;
+
+// Remote Docker socket forwarded over ssh
+const REMOTE_SOCKET: &str = "/var/run/docker.sock";
+
+// Local sockets of established tunnels by ssh destination, so reconnects reuse them
+static TUNNELS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+// Distinguishes the private directories of tunnels opened by this process
+static TUNNEL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// Split ssh://user@host:port into destination and optional port
+fn parse_destination(ssh_host: &str) -> (String, Option<String>) {
+    let destination = ssh_host.trim_start_matches("ssh://").trim_end_matches('/');
+    match destination.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => {
+            (host.to_string(), Some(port.to_string()))
+        }
+        _ => (destination.to_string(), None),
+    }
+}
+
+fn build_command(
+    ssh_host: &str,
+    ssh_key: &str,
+    ssh_known_hosts: &str,
+    local_socket: &str,
+) -> Command {
+    let (destination, port) = parse_destination(ssh_host);
+    let mut command = Command::new("ssh");
+    command.args([
+        "-N",
+        "-T",
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "ExitOnForwardFailure=yes",
+        "-o",
+        "ServerAliveInterval=10",
+        "-o",
+        "ServerAliveCountMax=3",
+        "-o",
+        "StrictHostKeyChecking=yes",
+        "-o",
+        "StreamLocalBindUnlink=yes",
+    ]);
+    if !ssh_known_hosts.is_empty() {
+        command.args(["-o", &format!("UserKnownHostsFile={}", ssh_known_hosts)]);
+    }
+    if !ssh_key.is_empty() {
+        command.args(["-o", "IdentitiesOnly=yes", "-i", ssh_key]);
+    }
+    if let Some(p) = port {
+        command.args(["-p", &p]);
+    }
+    command.args([
+        "-L",
+        &format!("{}:{}", local_socket, REMOTE_SOCKET),
+        &destination,
+    ]);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .kill_on_drop(true);
+    command
+}
+
+// Create a directory under TMPDIR only this user may enter, so the forwarded socket is not
+// reachable by others
+#[cfg(unix)]
+fn private_dir() -> Result<PathBuf, std::io::Error> {
+    use std::os::unix::fs::DirBuilderExt;
+    let dir = std::env::temp_dir().join(format!(
+        "docker-autoheal-{}-{}",
+        std::process::id(),
+        TUNNEL_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    // Typically read-only file systems without a tmpfs at TMPDIR
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => Ok(dir),
+        Err(e) => Err(std::io::Error::new(
+            e.kind(),
+            format!("unable to create {}: {}", dir.display(), e),
+        )),
+    }
+}
+
+#[cfg(not(unix))]
+fn private_dir() -> Result<PathBuf, std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "ssh tunnels require unix sockets",
+    ))
+}
+
+// Whether the forwarded socket accepts connections
+#[cfg(unix)]
+async fn is_listening(path: &Path) -> bool {
+    tokio::net::UnixStream::connect(path).await.is_ok()
+}
+
+#[cfg(not(unix))]
+async fn is_listening(_path: &Path) -> bool {
+    false
+}
+
+// Forward the remote Docker socket to a local unix socket, keeping the tunnel alive
+pub async fn open_tunnel(
+    ssh_host: String,
+    ssh_key: String,
+    ssh_known_hosts: String,
+    tcp_timeout: u64,
+) -> Result<String, std::io::Error> {
+    let tunnels = TUNNELS.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(socket) = tunnels.lock().unwrap().get(&ssh_host) {
+        return Ok(socket.clone());
+    }
+
+    let local_socket = private_dir()?.join("docker.sock");
+    let socket = local_socket.to_string_lossy().to_string();
+
+    let msg0 = format!("Opening ssh tunnel to {} via {}", ssh_host, socket);
+    log_message(&msg0, INFO).await;
+    let mut child = build_command(&ssh_host, &ssh_key, &ssh_known_hosts, &socket).spawn()?;
+
+    // Wait for the forward to accept connections
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(tcp_timeout);
+    while !is_listening(&local_socket).await {
+        if let Some(status) = child.try_wait()? {
+            return Err(std::io::Error::other(format!(
+                "ssh exited before the tunnel was established ({})",
+                status
+            )));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "ssh tunnel was not established in time",
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    tunnels
+        .lock()
+        .unwrap()
+        .insert(ssh_host.clone(), socket.clone());
+
+    // Re-establish the tunnel whenever ssh exits
+    let path = socket.clone();
+    tokio::task::spawn(async move {
+        loop {
+            let msg0 = match child.wait().await {
+                Ok(status) => format!("ssh tunnel to {} exited ({})", ssh_host, status),
+                Err(e) => format!("ssh tunnel to {} failed: {}", ssh_host, e),
+            };
+            log_message(&msg0, WARNING).await;
+            tokio::time::sleep(Duration::from_secs(tcp_timeout)).await;
+            let msg1 = format!("Reconnecting ssh tunnel to {}", ssh_host);
+            log_message(&msg1, INFO).await;
+            child = loop {
+                match build_command(&ssh_host, &ssh_key, &ssh_known_hosts, &path).spawn() {
+                    Ok(c) => break c,
+                    Err(e) => {
+                        let msg0 = format!("Could not start ssh tunnel to {}: {}", ssh_host, e);
+                        log_message(&msg0, ERROR).await;
+                        tokio::time::sleep(Duration::from_secs(tcp_timeout)).await;
+                    }
+                }
+            };
+        }
+    });
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .as_std()
+            .get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_destination_splits_port() {
+        assert_eq!(
+            parse_destination("ssh://user@host:2222"),
+            ("user@host".to_string(), Some("2222".to_string()))
+        );
+        assert_eq!(
+            parse_destination("ssh://user@host/"),
+            ("user@host".to_string(), None)
+        );
+        assert_eq!(parse_destination("host"), ("host".to_string(), None));
+        // Not a port, e.g. the end of an IPv6 address
+        assert_eq!(
+            parse_destination("ssh://user@fe80::1:abcd"),
+            ("user@fe80::1:abcd".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn build_command_forwards_to_unix_socket() {
+        let command = build_command(
+            "ssh://autoheal@docker1:2222",
+            "/keys/id_ed25519",
+            "/keys/known_hosts",
+            "/tmp/docker-autoheal-1-0/docker.sock",
+        );
+        let args = args(&command);
+        assert_eq!(command.as_std().get_program(), "ssh");
+        for option in [
+            "BatchMode=yes",
+            "ExitOnForwardFailure=yes",
+            "StrictHostKeyChecking=yes",
+            "StreamLocalBindUnlink=yes",
+            "UserKnownHostsFile=/keys/known_hosts",
+            "IdentitiesOnly=yes",
+        ] {
+            assert!(args.contains(&option.to_string()), "missing {}", option);
+        }
+        assert!(args.ends_with(&[
+            "-i".to_string(),
+            "/keys/id_ed25519".to_string(),
+            "-p".to_string(),
+            "2222".to_string(),
+            "-L".to_string(),
+            "/tmp/docker-autoheal-1-0/docker.sock:/var/run/docker.sock".to_string(),
+            "autoheal@docker1".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn build_command_omits_unset_options() {
+        let args = args(&build_command(
+            "ssh://docker1",
+            "",
+            "",
+            "/run/a/docker.sock",
+        ));
+        assert!(!args.iter().any(|a| a.starts_with("UserKnownHostsFile")));
+        assert!(!args.contains(&"-i".to_string()));
+        assert!(!args.contains(&"-p".to_string()));
+        assert!(!args.iter().any(|a| a.contains("127.0.0.1")));
+        assert!(args.ends_with(&[
+            "-L".to_string(),
+            "/run/a/docker.sock:/var/run/docker.sock".to_string(),
+            "docker1".to_string(),
+        ]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_dir_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = private_dir().unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    // Requires a local sshd forwarding to a Docker socket, e.g.
+    // AUTOHEAL_TEST_SSH_HOST=ssh://$USER@localhost cargo test -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn open_tunnel_against_local_sshd() {
+        let ssh_host = std::env::var("AUTOHEAL_TEST_SSH_HOST").unwrap();
+        let ssh_key = std::env::var("AUTOHEAL_TEST_SSH_KEY").unwrap_or_default();
+        let ssh_known_hosts = std::env::var("AUTOHEAL_TEST_SSH_KNOWN_HOSTS").unwrap_or_default();
+        let socket = open_tunnel(ssh_host, ssh_key, ssh_known_hosts, 10)
+            .await
+            .unwrap();
+        assert!(is_listening(Path::new(&socket)).await);
+    }
+}
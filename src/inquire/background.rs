@@ -0,0 +1,64 @@
+use crate::report::logging::ENDPOINT;
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+// Task following Docker in the background for the monitored endpoint, aborted when dropped
+// Owners keep it alongside the history it fills, which the task only holds a weak reference to,
+// so dropping the history stops the task
+pub struct BackgroundTask(JoinHandle<()>);
+
+impl BackgroundTask {
+    pub fn spawn<F>(task: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let endpoint = ENDPOINT.try_with(|e| e.clone()).unwrap_or_default();
+        BackgroundTask(tokio::task::spawn(ENDPOINT.scope(endpoint, task)))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+impl Drop for BackgroundTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn dropping_owner_stops_task() {
+        // Owner holding the task that fills it, as the history lists do
+        #[derive(Default)]
+        struct OwnerList {
+            task: Option<BackgroundTask>,
+            ticks: usize,
+        }
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let owner = Arc::new(Mutex::new(OwnerList::default()));
+        let shared = Arc::downgrade(&owner);
+        let task = BackgroundTask::spawn(async move {
+            let _tx = tx;
+            loop {
+                match shared.upgrade() {
+                    Some(owner) => owner.lock().unwrap().ticks += 1,
+                    None => return,
+                }
+                tokio::task::yield_now().await;
+            }
+        });
+        owner.lock().unwrap().task = Some(task);
+        while owner.lock().unwrap().ticks == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!owner.lock().unwrap().task.as_ref().unwrap().is_finished());
+        drop(owner);
+        assert!(rx.await.is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use bollard::{container::ListContainersOptions, Docker};
+use std::collections::{HashMap, HashSet};
+
+// Container of a compose project along with the services it depends on
+#[derive(Clone)]
+pub struct ComposeMemberList {
+    pub name: String,
+    pub id: String,
+    pub service: String,
+    pub depends_on: Vec<String>,
+}
+
+// Parse com.docker.compose.depends_on, e.g. db:service_healthy:false,cache:service_started:true
+fn parse_depends_on(label: &str) -> Vec<String> {
+    label
+        .split(',')
+        .filter_map(|d| d.split(':').next())
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+// Gather the running containers of a compose project, excluding one-off `compose run` containers
+// Stopped services are left alone, as restarting them would start them
+pub async fn compose_members(docker: &Docker, project: &str) -> Vec<ComposeMemberList> {
+    let mut filters = HashMap::new();
+    let label = format!("com.docker.compose.project={}", project);
+    filters.insert("label", vec![label.as_str()]);
+    filters.insert("status", vec!["running"]);
+    let container_options = Some(ListContainersOptions {
+        filters,
+        ..Default::default()
+    });
+    match docker.list_containers(container_options).await {
+        Ok(list) => list
+            .into_iter()
+            .filter_map(|c| {
+                let labels = c.labels.unwrap_or_default();
+                if labels
+                    .get("com.docker.compose.oneoff")
+                    .is_some_and(|o| o.to_lowercase() == "true")
+                {
+                    return None;
+                }
+                Some(ComposeMemberList {
+                    name: c.names?.first()?.trim_matches('/').to_string(),
+                    id: c.id?.chars().take(12).collect(),
+                    service: labels.get("com.docker.compose.service")?.clone(),
+                    depends_on: labels
+                        .get("com.docker.compose.depends_on")
+                        .map(|d| parse_depends_on(d))
+                        .unwrap_or_default(),
+                })
+            })
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+// Select the containers to restart for a scope, ordered so that dependencies come first
+pub fn restart_order(
+    members: Vec<ComposeMemberList>,
+    service: &str,
+    scope: &str,
+) -> Vec<ComposeMemberList> {
+    // Determine the services in scope
+    let selected: HashSet<String> = match scope {
+        "project" => members.iter().map(|m| m.service.clone()).collect(),
+        _ => {
+            // The service plus everything that transitively depends on it
+            let mut selected = HashSet::from([service.to_string()]);
+            loop {
+                let dependents: Vec<String> = members
+                    .iter()
+                    .filter(|m| !selected.contains(&m.service))
+                    .filter(|m| m.depends_on.iter().any(|d| selected.contains(d)))
+                    .map(|m| m.service.clone())
+                    .collect();
+                if dependents.is_empty() {
+                    break selected;
+                }
+                selected.extend(dependents);
+            }
+        }
+    };
+    let mut pending: Vec<ComposeMemberList> = members
+        .into_iter()
+        .filter(|m| selected.contains(&m.service))
+        .collect();
+    pending.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // Release services once none of their in-scope dependencies remain pending
+    let mut ordered = vec![];
+    while !pending.is_empty() {
+        let waiting: HashSet<String> = pending.iter().map(|m| m.service.clone()).collect();
+        let (ready, blocked): (Vec<_>, Vec<_>) = pending.into_iter().partition(|m| {
+            !m.depends_on
+                .iter()
+                .any(|d| d != &m.service && waiting.contains(d))
+        });
+        // A dependency cycle cannot be ordered; restart the remainder as listed
+        if ready.is_empty() {
+            ordered.extend(blocked);
+            break;
+        }
+        ordered.extend(ready);
+        pending = blocked;
+    }
+    ordered
+}
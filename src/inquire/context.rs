@@ -0,0 +1,140 @@
+use crate::{log_message, ERROR};
+use serde_json::Value;
+use std::path::PathBuf;
+
+// Connection details as understood by the Docker CLI
+pub struct DockerHostList {
+    pub connection_type: String,
+    pub tcp_host: String,
+    pub tcp_port: String,
+    pub socket_path: String,
+    pub ssh_host: String,
+    pub pem_path: String,
+}
+
+// Directory holding the Docker CLI configuration
+fn config_dir() -> PathBuf {
+    match std::env::var("DOCKER_CONFIG") {
+        Ok(d) if !d.is_empty() => PathBuf::from(d),
+        _ => {
+            let home = std::env::var("HOME")
+                .or_else(|_| std::env::var("USERPROFILE"))
+                .unwrap_or_default();
+            PathBuf::from(home).join(".docker")
+        }
+    }
+}
+
+async fn read_json(path: PathBuf) -> Option<Value> {
+    let content = tokio::fs::read_to_string(path).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// Determine host and tls material of the active Docker CLI context
+async fn get_context() -> Option<(String, bool, String)> {
+    let config = config_dir();
+    let name = match std::env::var("DOCKER_CONTEXT") {
+        Ok(n) if !n.is_empty() => n,
+        _ => read_json(config.join("config.json"))
+            .await?
+            .get("currentContext")?
+            .as_str()?
+            .to_string(),
+    };
+    if name.is_empty() || name == "default" {
+        return None;
+    }
+    // Context directories are named by digest, so match on the stored name instead
+    let mut entries = tokio::fs::read_dir(config.join("contexts").join("meta"))
+        .await
+        .ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Some(meta) = read_json(entry.path().join("meta.json")).await else {
+            continue;
+        };
+        if meta.get("Name").and_then(|n| n.as_str()) != Some(name.as_str()) {
+            continue;
+        }
+        let docker = meta.get("Endpoints")?.get("docker")?;
+        let host = docker.get("Host")?.as_str()?.to_string();
+        let skip_tls_verify = docker
+            .get("SkipTLSVerify")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false);
+        let tls_path = config
+            .join("contexts")
+            .join("tls")
+            .join(entry.file_name())
+            .join("docker");
+        let pem_path = match !skip_tls_verify && tokio::fs::metadata(&tls_path).await.is_ok() {
+            true => tls_path.to_string_lossy().to_string(),
+            false => "".to_string(),
+        };
+        return Some((host, !pem_path.is_empty(), pem_path));
+    }
+    None
+}
+
+// Honour DOCKER_HOST, DOCKER_CERT_PATH, DOCKER_TLS_VERIFY and Docker CLI contexts
+pub async fn get_docker_host() -> Option<DockerHostList> {
+    let (host, tls_verify, pem_path) = match std::env::var("DOCKER_HOST") {
+        Ok(h) if !h.is_empty() => {
+            let tls_verify = std::env::var("DOCKER_TLS_VERIFY")
+                .is_ok_and(|v| !v.is_empty() && v != "0" && v.to_lowercase() != "false");
+            let pem_path = match std::env::var("DOCKER_CERT_PATH") {
+                Ok(p) if !p.is_empty() => p,
+                _ if tls_verify => config_dir().to_string_lossy().to_string(),
+                _ => "".to_string(),
+            };
+            (h, tls_verify, pem_path)
+        }
+        _ => get_context().await?,
+    };
+
+    let mut docker_host = DockerHostList {
+        connection_type: "local".to_string(),
+        tcp_host: "".to_string(),
+        tcp_port: "".to_string(),
+        socket_path: "".to_string(),
+        ssh_host: "".to_string(),
+        pem_path,
+    };
+    if let Some(path) = host.strip_prefix("unix://") {
+        docker_host.connection_type = "socket".to_string();
+        docker_host.socket_path = path.to_string();
+    } else if let Some(pipe) = host.strip_prefix("npipe://") {
+        // Named pipes only exist on Windows
+        if !cfg!(windows) {
+            let msg0 = format!(
+                "Unsupported Docker host ({}): named pipes are only available on Windows",
+                host
+            );
+            log_message(&msg0, ERROR).await;
+            std::process::exit(1);
+        }
+        docker_host.connection_type = "socket".to_string();
+        docker_host.socket_path = pipe.to_string();
+    } else if host.starts_with("ssh://") {
+        docker_host.connection_type = "ssh".to_string();
+        docker_host.ssh_host = host;
+    } else if let Some(address) = host
+        .strip_prefix("tcp://")
+        .or_else(|| host.strip_prefix("http://"))
+        .or_else(|| host.strip_prefix("https://"))
+    {
+        let address = address.trim_end_matches('/');
+        let (tcp_host, tcp_port) = match address.rsplit_once(':') {
+            Some((h, p)) => (h.to_string(), p.to_string()),
+            None => (address.to_string(), "".to_string()),
+        };
+        docker_host.connection_type = match tls_verify || host.starts_with("https://") {
+            true => "ssl".to_string(),
+            false => "http".to_string(),
+        };
+        docker_host.tcp_host = tcp_host;
+        docker_host.tcp_port = tcp_port;
+    } else {
+        return None;
+    }
+    Some(docker_host)
+}
@@ -0,0 +1,55 @@
+use super::compose::ComposeMemberList;
+use bollard::{container::ListContainersOptions, Docker};
+use std::collections::HashMap;
+
+// Parse autoheal.depends.on, e.g. db,cache
+pub fn get_depends_on(labels: &HashMap<String, String>) -> Vec<String> {
+    labels
+        .get("autoheal.depends.on")
+        .map(|d| {
+            d.split(',')
+                .map(|d| d.trim().trim_matches('/').to_string())
+                .filter(|d| !d.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Gather running, monitored containers that declare dependencies and may be restarted
+pub async fn get_dependants(docker: &Docker, monitor_all: bool) -> Vec<ComposeMemberList> {
+    let mut filters = HashMap::new();
+    filters.insert("label", vec!["autoheal.depends.on"]);
+    let container_options = Some(ListContainersOptions {
+        filters,
+        ..Default::default()
+    });
+    match docker.list_containers(container_options).await {
+        Ok(list) => list
+            .into_iter()
+            .filter_map(|c| {
+                let labels = c.labels.unwrap_or_default();
+                if labels
+                    .get("autoheal.restart.enable")
+                    .is_some_and(|v| v.parse() == Ok(false))
+                {
+                    return None;
+                }
+                let monitored = match labels.get("autoheal.monitor.enable") {
+                    Some(v) => v.parse().unwrap_or(monitor_all),
+                    None => monitor_all,
+                };
+                if !monitored {
+                    return None;
+                }
+                let name = c.names?.first()?.trim_matches('/').to_string();
+                Some(ComposeMemberList {
+                    id: c.id?.chars().take(12).collect(),
+                    service: name.clone(),
+                    name,
+                    depends_on: get_depends_on(&labels),
+                })
+            })
+            .collect(),
+        Err(_) => vec![],
+    }
+}
@@ -0,0 +1,158 @@
+use super::environment::VariablesList;
+use crate::{
+    execute::connect::ConnectionVariablesList, log_message, ALLOWED_CONNECTION_TYPES,
+    ALLOWED_RUNTIMES, ERROR,
+};
+use serde::Deserialize;
+
+// Endpoint as declared in the hosts file; unset values inherit the global configuration
+#[derive(Deserialize)]
+struct Endpoint {
+    name: Option<String>,
+    connection_type: Option<String>,
+    tcp_host: Option<String>,
+    tcp_port: Option<u64>,
+    tcp_timeout: Option<u64>,
+    pem_path: Option<String>,
+    tls_key_path: Option<String>,
+    tls_cert_path: Option<String>,
+    tls_ca_path: Option<String>,
+    socket_path: Option<String>,
+    ssh_host: Option<String>,
+    ssh_key: Option<String>,
+    ssh_known_hosts: Option<String>,
+    runtime: Option<String>,
+    interval: Option<u64>,
+    stop_timeout: Option<isize>,
+    apprise_url: Option<String>,
+    webhook_key: Option<String>,
+    webhook_url: Option<String>,
+    post_action: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct EndpointList {
+    pub name: String,
+    pub connection: ConnectionVariablesList,
+    pub runtime: String,
+    pub interval: u64,
+    pub stop_timeout: isize,
+    pub apprise_url: String,
+    pub webhook_key: String,
+    pub webhook_url: String,
+    pub post_action: String,
+}
+
+// Read the list of Docker hosts to monitor from a JSON file
+pub async fn get_endpoints(var: &VariablesList) -> Vec<EndpointList> {
+    let content = match tokio::fs::read_to_string(&var.hosts_file).await {
+        Ok(c) => c,
+        Err(e) => {
+            let msg0 = format!("Could not read hosts file ({})", var.hosts_file);
+            log_message(&msg0, ERROR).await;
+            panic!("{e}")
+        }
+    };
+    let declared: Vec<Endpoint> = match serde_json::from_str(&content) {
+        Ok(d) => d,
+        Err(e) => {
+            let msg0 = format!("Could not parse hosts file ({})", var.hosts_file);
+            log_message(&msg0, ERROR).await;
+            panic!("{e}")
+        }
+    };
+
+    let mut endpoints = vec![];
+    for endpoint in declared {
+        let connection_type = endpoint
+            .connection_type
+            .unwrap_or(var.connection_type.clone())
+            .to_lowercase();
+        if !ALLOWED_CONNECTION_TYPES.contains(&connection_type.as_str()) {
+            let msg0 = format!(
+                "Unexpected connection-type ({}) in hosts file: Expected one of {}",
+                connection_type,
+                ALLOWED_CONNECTION_TYPES.join(",")
+            );
+            log_message(&msg0, ERROR).await;
+            continue;
+        }
+        let runtime = endpoint
+            .runtime
+            .unwrap_or(var.runtime.clone())
+            .to_lowercase();
+        if !ALLOWED_RUNTIMES.contains(&runtime.as_str()) {
+            let msg0 = format!(
+                "Unexpected runtime ({}) in hosts file: Expected one of {}",
+                runtime,
+                ALLOWED_RUNTIMES.join(",")
+            );
+            log_message(&msg0, ERROR).await;
+            continue;
+        }
+        let tcp_host = endpoint.tcp_host.unwrap_or("localhost".to_string());
+        let tcp_port = endpoint.tcp_port.unwrap_or(match connection_type.as_str() {
+            "ssl" => 2376,
+            &_ => 2375,
+        });
+        let (mut key_path, mut cert_path, mut ca_path) = match endpoint.pem_path {
+            Some(p) => (
+                format!("{}/key.pem", p),
+                format!("{}/cert.pem", p),
+                format!("{}/ca.pem", p),
+            ),
+            None => (
+                var.key_path.clone(),
+                var.cert_path.clone(),
+                var.ca_path.clone(),
+            ),
+        };
+        // Individually specified files take precedence over pem_path
+        if let Some(p) = endpoint.tls_key_path {
+            key_path = p;
+        }
+        if let Some(p) = endpoint.tls_cert_path {
+            cert_path = p;
+        }
+        if let Some(p) = endpoint.tls_ca_path {
+            ca_path = p;
+        }
+        let ssh_host = endpoint.ssh_host.unwrap_or(var.ssh_host.clone());
+        let name = endpoint.name.unwrap_or(match connection_type.as_str() {
+            "http" | "ssl" => tcp_host.clone(),
+            "ssh" => ssh_host.clone(),
+            &_ => connection_type.clone(),
+        });
+        endpoints.push(EndpointList {
+            name,
+            connection: ConnectionVariablesList {
+                connection_type,
+                tcp_address: format!("{}:{}", tcp_host, tcp_port),
+                tcp_timeout: endpoint.tcp_timeout.unwrap_or(var.tcp_timeout),
+                key_path,
+                cert_path,
+                ca_path,
+                socket_path: endpoint.socket_path.unwrap_or(var.socket_path.clone()),
+                ssh_host,
+                ssh_key: endpoint.ssh_key.unwrap_or(var.ssh_key.clone()),
+                ssh_known_hosts: endpoint
+                    .ssh_known_hosts
+                    .unwrap_or(var.ssh_known_hosts.clone()),
+            },
+            runtime,
+            interval: endpoint.interval.unwrap_or(var.interval),
+            stop_timeout: endpoint.stop_timeout.unwrap_or(var.stop_timeout),
+            apprise_url: endpoint.apprise_url.unwrap_or(var.apprise_url.clone()),
+            webhook_key: endpoint.webhook_key.unwrap_or(var.webhook_key.clone()),
+            webhook_url: endpoint.webhook_url.unwrap_or(var.webhook_url.clone()),
+            post_action: endpoint.post_action.unwrap_or(var.post_action.clone()),
+        });
+    }
+    // Monitoring nothing would exit at once, as if successful
+    if endpoints.is_empty() {
+        let msg0 = format!("No valid Docker hosts in hosts file ({})", var.hosts_file);
+        log_message(&msg0, ERROR).await;
+        panic!("{msg0}")
+    }
+    endpoints
+}
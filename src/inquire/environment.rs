@@ -1,5 +1,9 @@
-use super::options::OptionsList;
-use crate::{log_message, ALLOWED_CONNECTION_TYPES, ERROR, WARNING};
+use super::{context::get_docker_host, options::OptionsList, schedule::check_schedule};
+use crate::{
+    log_message, ALLOWED_CONNECTION_TYPES, ALLOWED_IDENTITY_TYPES, ALLOWED_POD_RESTARTS,
+    ALLOWED_RESTART_SCOPES, ALLOWED_RUNTIMES, ALLOWED_SWARM_ACTIONS, ERROR, INFO, LOG_PATH,
+    WARNING,
+};
 
 pub struct VariablesList {
     pub connection_type: String,
@@ -11,30 +15,105 @@ pub struct VariablesList {
     pub key_path: String,
     pub cert_path: String,
     pub ca_path: String,
+    pub socket_path: String,
     pub apprise_url: String,
     pub webhook_key: String,
     pub webhook_url: String,
     pub post_action: String,
     pub log_all: bool,
     pub monitor_all: bool,
+    pub monitor_exited: bool,
+    pub exited_budget: u64,
+    pub failing_threshold: i64,
+    pub unhealthy_duration: u64,
+    pub log_debug: bool,
+    pub ignore_output: String,
+    pub log_tail: u64,
+    pub log_tail_bytes: u64,
+    pub log_tail_notify: bool,
+    pub diagnostics: bool,
+    pub diagnostics_export: bool,
+    pub log_pattern: String,
+    pub log_pattern_threshold: usize,
+    pub log_pattern_window: u64,
+    pub skip_restart_no: bool,
+    pub deploy_window: u64,
+    pub maintenance: String,
+    pub quiet_hours: String,
     pub log_persist: bool,
+    pub log_path: String,
+    pub log_max_age: u64,
+    pub log_max_size: u64,
+    pub log_max_files: u64,
+    pub log_max_artifacts: u64,
+    pub log_compress: bool,
+    pub log_quarantine: bool,
+    pub identity_type: String,
+    pub hosts_file: String,
+    pub runtime: String,
+    pub pod_restart: String,
+    pub swarm_action: String,
+    pub restart_scope: String,
+    pub ssh_host: String,
+    pub ssh_key: String,
+    pub ssh_known_hosts: String,
 }
 
 // Get environment variable
 fn get_env(key: &str, default: &str) -> String {
     let env_var = std::env::var(key.to_uppercase()).unwrap_or(default.to_string());
-    if key.contains("WEBHOOK") || key.contains("APPRISE") {
+    if key.contains("WEBHOOK")
+        || key.contains("APPRISE")
+        || key.ends_with("_PATH")
+        || key.ends_with("_FILE")
+        || key.contains("SSH")
+        || key.contains("OUTPUT")
+        || key.contains("PATTERN")
+    {
         env_var
     } else {
         env_var.to_lowercase()
     }
 }
 
+// Determine persistent log directory, always with a trailing separator
+pub fn get_log_path(opt: Option<String>) -> String {
+    let mut log_path: String = match opt {
+        None => get_env("AUTOHEAL_LOG_PATH", LOG_PATH),
+        Some(o) => o,
+    };
+    if !log_path.ends_with('/') {
+        log_path.push('/');
+    }
+    log_path
+}
+
 // Determine if we have valid arguments, need to check env, or use defaults
 pub async fn get_var(opt: OptionsList) -> VariablesList {
+    // Docker client environment and contexts provide defaults; AUTOHEAL_* variables take precedence
+    let docker_host = get_docker_host().await;
+    if let Some(d) = &docker_host {
+        let msg0 = format!(
+            "Docker client environment found; defaulting to connection-type {}",
+            d.connection_type
+        );
+        log_message(&msg0, INFO).await;
+    }
+    let docker_default =
+        |f: fn(&super::context::DockerHostList) -> &String, default: &str| match docker_host
+            .as_ref()
+            .map(f)
+        {
+            Some(v) if !v.is_empty() => v.clone(),
+            _ => default.to_string(),
+        };
+
     let autoheal_connection_type: String = match opt.connection_type {
         None => {
-            let env_connection_type = get_env("AUTOHEAL_CONNECTION_TYPE", "local");
+            let env_connection_type = get_env(
+                "AUTOHEAL_CONNECTION_TYPE",
+                &docker_default(|d| &d.connection_type, "local"),
+            );
             match ALLOWED_CONNECTION_TYPES.contains(&env_connection_type.as_str()) {
                 true => env_connection_type,
                 false => {
@@ -97,19 +176,332 @@ pub async fn get_var(opt: OptionsList) -> VariablesList {
     if opt.monitor_all {
         autoheal_monitor_all = true
     }
+    let mut autoheal_monitor_exited = get_env("AUTOHEAL_MONITOR_EXITED", "false") == "true";
+    if opt.monitor_exited {
+        autoheal_monitor_exited = true
+    }
+    let autoheal_exited_budget: u64 = match opt.exited_budget {
+        None => get_env("AUTOHEAL_EXITED_BUDGET", "3").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                3
+            }
+        },
+    };
+    let autoheal_failing_threshold: i64 = match opt.failing_threshold {
+        None => get_env("AUTOHEAL_FAILING_THRESHOLD", "1").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                1
+            }
+        },
+    };
+    let autoheal_unhealthy_duration: u64 = match opt.unhealthy_duration {
+        None => get_env("AUTOHEAL_UNHEALTHY_DURATION", "0").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                0
+            }
+        },
+    };
+    let mut autoheal_log_debug = get_env("AUTOHEAL_LOG_DEBUG", "false") == "true";
+    if opt.log_debug {
+        autoheal_log_debug = true
+    }
+    let autoheal_ignore_output: String = match opt.ignore_output {
+        None => get_env("AUTOHEAL_IGNORE_OUTPUT", ""),
+        Some(o) => o,
+    };
+    let autoheal_log_tail: u64 = match opt.log_tail {
+        None => get_env("AUTOHEAL_LOG_TAIL", "0").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                0
+            }
+        },
+    };
+    let autoheal_log_tail_bytes: u64 = match opt.log_tail_bytes {
+        None => get_env("AUTOHEAL_LOG_TAIL_BYTES", "65536").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                65536
+            }
+        },
+    };
+    let mut autoheal_log_tail_notify = get_env("AUTOHEAL_LOG_TAIL_NOTIFY", "false") == "true";
+    if opt.log_tail_notify {
+        autoheal_log_tail_notify = true
+    }
+    let mut autoheal_diagnostics = get_env("AUTOHEAL_DIAGNOSTICS", "false") == "true";
+    if opt.diagnostics {
+        autoheal_diagnostics = true
+    }
+    let mut autoheal_diagnostics_export = get_env("AUTOHEAL_DIAGNOSTICS_EXPORT", "false") == "true";
+    if opt.diagnostics_export {
+        autoheal_diagnostics_export = true
+    }
+    let autoheal_log_pattern: String = match opt.log_pattern {
+        None => get_env("AUTOHEAL_LOG_PATTERN", ""),
+        Some(o) => o,
+    };
+    let autoheal_log_pattern_threshold: usize = match opt.log_pattern_threshold {
+        None => get_env("AUTOHEAL_LOG_PATTERN_THRESHOLD", "1")
+            .parse()
+            .unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                1
+            }
+        },
+    };
+    let mut autoheal_skip_restart_no = get_env("AUTOHEAL_SKIP_RESTART_NO", "false") == "true";
+    if opt.skip_restart_no {
+        autoheal_skip_restart_no = true
+    }
+    let autoheal_deploy_window: u64 = match opt.deploy_window {
+        None => get_env("AUTOHEAL_DEPLOY_WINDOW", "120").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                120
+            }
+        },
+    };
+    let autoheal_maintenance: String = match opt.maintenance {
+        None => get_env("AUTOHEAL_MAINTENANCE", ""),
+        Some(o) => o,
+    };
+    let autoheal_maintenance = match check_schedule(&autoheal_maintenance) {
+        Ok(()) => autoheal_maintenance,
+        Err(e) => {
+            let msg0 = format!(
+                "Unexpected maintenance ({}): {}; using default ()",
+                autoheal_maintenance, e
+            );
+            log_message(&msg0, WARNING).await;
+            "".to_string()
+        }
+    };
+    let autoheal_quiet_hours: String = match opt.quiet_hours {
+        None => get_env("AUTOHEAL_QUIET_HOURS", ""),
+        Some(o) => o,
+    };
+    let autoheal_quiet_hours = match check_schedule(&autoheal_quiet_hours) {
+        Ok(()) => autoheal_quiet_hours,
+        Err(e) => {
+            let msg0 = format!(
+                "Unexpected quiet-hours ({}): {}; using default ()",
+                autoheal_quiet_hours, e
+            );
+            log_message(&msg0, WARNING).await;
+            "".to_string()
+        }
+    };
+    let autoheal_log_pattern_window: u64 = match opt.log_pattern_window {
+        None => get_env("AUTOHEAL_LOG_PATTERN_WINDOW", "60")
+            .parse()
+            .unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                60
+            }
+        },
+    };
     let mut autoheal_log_persist = get_env("AUTOHEAL_LOG_PERSIST", "false") == "true";
     if opt.log_persist {
         autoheal_log_persist = true
     }
+    let autoheal_log_path = get_log_path(opt.log_path);
+
+    // Autoheal log retention variables
+    let autoheal_log_max_age: u64 = match opt.log_max_age {
+        None => get_env("AUTOHEAL_LOG_MAX_AGE", "0").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                0
+            }
+        },
+    };
+    let autoheal_log_max_size: u64 = match opt.log_max_size {
+        None => get_env("AUTOHEAL_LOG_MAX_SIZE", "0").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                0
+            }
+        },
+    };
+    let autoheal_log_max_files: u64 = match opt.log_max_files {
+        None => get_env("AUTOHEAL_LOG_MAX_FILES", "5").parse().unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                5
+            }
+        },
+    };
+    let autoheal_log_max_artifacts: u64 = match opt.log_max_artifacts {
+        None => get_env("AUTOHEAL_LOG_MAX_ARTIFACTS", "100")
+            .parse()
+            .unwrap(),
+        Some(o) => match o.parse() {
+            Ok(a) => a,
+            Err(e) => {
+                let msg0 = format!("Unexpected value; using default: {}", e);
+                log_message(&msg0, WARNING).await;
+                100
+            }
+        },
+    };
+    let mut autoheal_log_compress = get_env("AUTOHEAL_LOG_COMPRESS", "false") == "true";
+    if opt.log_compress {
+        autoheal_log_compress = true
+    }
+    let mut autoheal_log_quarantine = get_env("AUTOHEAL_LOG_QUARANTINE", "false") == "true";
+    if opt.log_quarantine {
+        autoheal_log_quarantine = true
+    }
+    let autoheal_identity_type: String = match opt.identity {
+        None => {
+            let env_identity = get_env("AUTOHEAL_IDENTITY", "name");
+            match ALLOWED_IDENTITY_TYPES.contains(&env_identity.as_str()) {
+                true => env_identity,
+                false => {
+                    let msg0 = format!(
+                        "Unexpected identity ({}): Expected one of {}; using default (name)",
+                        env_identity,
+                        ALLOWED_IDENTITY_TYPES.join(",")
+                    );
+                    log_message(&msg0, WARNING).await;
+                    "name".to_string()
+                }
+            }
+        }
+        Some(o) => o,
+    };
+    let autoheal_runtime: String = match opt.runtime {
+        None => {
+            let env_runtime = get_env("AUTOHEAL_RUNTIME", "auto");
+            match ALLOWED_RUNTIMES.contains(&env_runtime.as_str()) {
+                true => env_runtime,
+                false => {
+                    let msg0 = format!(
+                        "Unexpected runtime ({}): Expected one of {}; using default (auto)",
+                        env_runtime,
+                        ALLOWED_RUNTIMES.join(",")
+                    );
+                    log_message(&msg0, WARNING).await;
+                    "auto".to_string()
+                }
+            }
+        }
+        Some(o) => o,
+    };
+    let autoheal_pod_restart: String = match opt.pod_restart {
+        None => {
+            let env_pod_restart = get_env("AUTOHEAL_POD_RESTART", "container");
+            match ALLOWED_POD_RESTARTS.contains(&env_pod_restart.as_str()) {
+                true => env_pod_restart,
+                false => {
+                    let msg0 = format!(
+                        "Unexpected pod restart ({}): Expected one of {}; using default (container)",
+                        env_pod_restart,
+                        ALLOWED_POD_RESTARTS.join(",")
+                    );
+                    log_message(&msg0, WARNING).await;
+                    "container".to_string()
+                }
+            }
+        }
+        Some(o) => o,
+    };
+    let autoheal_swarm_action: String = match opt.swarm_action {
+        None => {
+            let env_swarm_action = get_env("AUTOHEAL_SWARM_ACTION", "restart");
+            match ALLOWED_SWARM_ACTIONS.contains(&env_swarm_action.as_str()) {
+                true => env_swarm_action,
+                false => {
+                    let msg0 = format!(
+                        "Unexpected swarm action ({}): Expected one of {}; using default (restart)",
+                        env_swarm_action,
+                        ALLOWED_SWARM_ACTIONS.join(",")
+                    );
+                    log_message(&msg0, WARNING).await;
+                    "restart".to_string()
+                }
+            }
+        }
+        Some(o) => o,
+    };
+    let autoheal_restart_scope: String = match opt.restart_scope {
+        None => {
+            let env_restart_scope = get_env("AUTOHEAL_RESTART_SCOPE", "container");
+            match ALLOWED_RESTART_SCOPES.contains(&env_restart_scope.as_str()) {
+                true => env_restart_scope,
+                false => {
+                    let msg0 = format!(
+                        "Unexpected restart scope ({}): Expected one of {}; using default (container)",
+                        env_restart_scope,
+                        ALLOWED_RESTART_SCOPES.join(",")
+                    );
+                    log_message(&msg0, WARNING).await;
+                    "container".to_string()
+                }
+            }
+        }
+        Some(o) => o,
+    };
+    let autoheal_hosts_file: String = match opt.hosts_file {
+        None => get_env("AUTOHEAL_HOSTS_FILE", ""),
+        Some(o) => o,
+    };
 
     // Autoheal tcp variables
     let autoheal_tcp_host: String = match opt.tcp_host {
-        None => get_env("AUTOHEAL_TCP_HOST", "localhost"),
+        None => get_env(
+            "AUTOHEAL_TCP_HOST",
+            &docker_default(|d| &d.tcp_host, "localhost"),
+        ),
         Some(o) => o,
     };
     let autoheal_tcp_port: u64 = match autoheal_connection_type.as_str() {
         "ssl" => match opt.tcp_port {
-            None => get_env("AUTOHEAL_TCP_PORT", "2376").parse().unwrap(),
+            None => get_env(
+                "AUTOHEAL_TCP_PORT",
+                &docker_default(|d| &d.tcp_port, "2376"),
+            )
+            .parse()
+            .unwrap(),
             Some(o) => match o.parse() {
                 Ok(a) => a,
                 Err(e) => {
@@ -120,7 +512,12 @@ pub async fn get_var(opt: OptionsList) -> VariablesList {
             },
         },
         &_ => match opt.tcp_port {
-            None => get_env("AUTOHEAL_TCP_PORT", "2375").parse().unwrap(),
+            None => get_env(
+                "AUTOHEAL_TCP_PORT",
+                &docker_default(|d| &d.tcp_port, "2375"),
+            )
+            .parse()
+            .unwrap(),
             Some(o) => match o.parse() {
                 Ok(a) => a,
                 Err(e) => {
@@ -146,12 +543,56 @@ pub async fn get_var(opt: OptionsList) -> VariablesList {
 
     // Autoheal ssl variables
     let autoheal_pem_path: String = match opt.key_path {
-        None => get_env("AUTOHEAL_PEM_PATH", "/opt/docker-autoheal/tls"),
+        None => get_env(
+            "AUTOHEAL_PEM_PATH",
+            &docker_default(|d| &d.pem_path, "/opt/docker-autoheal/tls"),
+        ),
+        Some(o) => o,
+    };
+    let autoheal_key_path: String = match opt.tls_key_path {
+        None => get_env(
+            "AUTOHEAL_TLS_KEY_PATH",
+            &format!("{}/key.pem", autoheal_pem_path),
+        ),
+        Some(o) => o,
+    };
+    let autoheal_cert_path: String = match opt.tls_cert_path {
+        None => get_env(
+            "AUTOHEAL_TLS_CERT_PATH",
+            &format!("{}/cert.pem", autoheal_pem_path),
+        ),
+        Some(o) => o,
+    };
+    let autoheal_ca_path: String = match opt.tls_ca_path {
+        None => get_env(
+            "AUTOHEAL_TLS_CA_PATH",
+            &format!("{}/ca.pem", autoheal_pem_path),
+        ),
+        Some(o) => o,
+    };
+
+    // Autoheal socket variables
+    let autoheal_socket_path: String = match opt.socket_path {
+        None => get_env(
+            "AUTOHEAL_SOCKET_PATH",
+            &docker_default(|d| &d.socket_path, ""),
+        ),
+        Some(o) => o,
+    };
+
+    // Autoheal ssh variables
+    let autoheal_ssh_host: String = match opt.ssh_host {
+        None => get_env("AUTOHEAL_SSH_HOST", &docker_default(|d| &d.ssh_host, "")),
+        Some(o) => o,
+    };
+    let autoheal_ssh_key: String = match opt.ssh_key {
+        None => get_env("AUTOHEAL_SSH_KEY", ""),
+        Some(o) => o,
+    };
+    let autoheal_ssh_known_hosts: String = match opt.ssh_known_hosts {
+        None => get_env("AUTOHEAL_SSH_KNOWN_HOSTS", ""),
         Some(o) => o,
     };
-    let autoheal_key_path: String = format!("{}/key.pem", autoheal_pem_path);
-    let autoheal_cert_path: String = format!("{}/cert.pem", autoheal_pem_path);
-    let autoheal_ca_path: String = format!("{}/ca.pem", autoheal_pem_path);
 
     // Webhook variables
     let autoheal_apprise_url: String = match opt.apprise_url {
@@ -177,12 +618,47 @@ pub async fn get_var(opt: OptionsList) -> VariablesList {
         key_path: autoheal_key_path,
         cert_path: autoheal_cert_path,
         ca_path: autoheal_ca_path,
+        socket_path: autoheal_socket_path,
         apprise_url: autoheal_apprise_url,
         webhook_key: autoheal_webhook_key,
         webhook_url: autoheal_webhook_url,
         post_action: autoheal_post_action,
         log_all: autoheal_log_all,
         monitor_all: autoheal_monitor_all,
+        monitor_exited: autoheal_monitor_exited,
+        exited_budget: autoheal_exited_budget,
+        failing_threshold: autoheal_failing_threshold,
+        unhealthy_duration: autoheal_unhealthy_duration,
+        log_debug: autoheal_log_debug,
+        ignore_output: autoheal_ignore_output,
+        log_tail: autoheal_log_tail,
+        log_tail_bytes: autoheal_log_tail_bytes,
+        log_tail_notify: autoheal_log_tail_notify,
+        diagnostics: autoheal_diagnostics,
+        diagnostics_export: autoheal_diagnostics_export,
+        log_pattern: autoheal_log_pattern,
+        log_pattern_threshold: autoheal_log_pattern_threshold,
+        log_pattern_window: autoheal_log_pattern_window,
+        skip_restart_no: autoheal_skip_restart_no,
+        deploy_window: autoheal_deploy_window,
+        maintenance: autoheal_maintenance,
+        quiet_hours: autoheal_quiet_hours,
         log_persist: autoheal_log_persist,
+        log_path: autoheal_log_path,
+        log_max_age: autoheal_log_max_age,
+        log_max_size: autoheal_log_max_size,
+        log_max_files: autoheal_log_max_files,
+        log_max_artifacts: autoheal_log_max_artifacts,
+        log_compress: autoheal_log_compress,
+        log_quarantine: autoheal_log_quarantine,
+        identity_type: autoheal_identity_type,
+        hosts_file: autoheal_hosts_file,
+        runtime: autoheal_runtime,
+        pod_restart: autoheal_pod_restart,
+        swarm_action: autoheal_swarm_action,
+        restart_scope: autoheal_restart_scope,
+        ssh_host: autoheal_ssh_host,
+        ssh_key: autoheal_ssh_key,
+        ssh_known_hosts: autoheal_ssh_known_hosts,
     }
 }
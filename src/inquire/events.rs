@@ -0,0 +1,334 @@
+use super::background::BackgroundTask;
+use crate::{log_message, WARNING};
+use bollard::{
+    models::{EventMessage, EventMessageTypeEnum},
+    system::EventsOptions,
+    Docker,
+};
+use futures::StreamExt;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+// Time after a compose container is removed during which its project is considered going down
+const COMPOSE_DOWN_WINDOW: Duration = Duration::from_secs(60);
+
+// Longest deploy activity is remembered for, regardless of the configured window
+const DEPLOY_HISTORY: Duration = Duration::from_secs(3600);
+
+// Containers autoheal stopped itself, by id, so their stop events are not taken as a request
+static AUTOHEAL_STOPS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+// Containers stopped on request and containers seen to die (by id), and when compose projects
+// last had a container removed
+// Deploy activity is kept by image pulled, container created (by name) and compose project
+#[derive(Default)]
+pub struct EventHistoryList {
+    follower: Option<BackgroundTask>,
+    stopped: HashSet<String>,
+    died: HashSet<String>,
+    removed: HashMap<String, Instant>,
+    pulled: HashMap<String, Instant>,
+    created: HashMap<String, (Instant, bool)>,
+    deployed: HashMap<String, Instant>,
+}
+
+// Reference an image with its tag, as pull events do, e.g. nginx becomes nginx:latest
+fn image_reference(image: &str) -> String {
+    let name = image.rsplit('/').next().unwrap_or(image);
+    match name.contains(':') || name.contains('@') {
+        true => image.to_string(),
+        false => format!("{}:latest", image),
+    }
+}
+
+fn autoheal_stops() -> &'static Mutex<HashSet<String>> {
+    AUTOHEAL_STOPS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+// Note a container autoheal is about to stop, until it is started or removed again
+pub fn expect_stop(id: &str) {
+    autoheal_stops().lock().unwrap().insert(id.to_string());
+}
+
+fn is_autoheal_stop(id: &str) -> bool {
+    autoheal_stops()
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|s| id.starts_with(s.as_str()))
+}
+
+fn forget_stop(id: &str) {
+    autoheal_stops()
+        .lock()
+        .unwrap()
+        .retain(|s| !id.starts_with(s.as_str()));
+}
+
+// Record container lifecycle events relevant to remediation
+fn record_event(history: &Mutex<EventHistoryList>, event: EventMessage) {
+    let Some(actor) = event.actor else {
+        return;
+    };
+    let id = actor.id.unwrap_or_default();
+    let attributes = actor.attributes.unwrap_or_default();
+    let project = attributes
+        .get("com.docker.compose.project")
+        .cloned()
+        .unwrap_or_default();
+    // One-off containers, e.g. of docker compose run --rm, neither deploy nor take down a project
+    let oneoff = attributes
+        .get("com.docker.compose.oneoff")
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+    let mut history = history.lock().unwrap();
+    let now = Instant::now();
+    history.pulled.retain(|_, p| p.elapsed() < DEPLOY_HISTORY);
+    history
+        .created
+        .retain(|_, (c, _)| c.elapsed() < DEPLOY_HISTORY);
+    history.deployed.retain(|_, d| d.elapsed() < DEPLOY_HISTORY);
+    if event.typ == Some(EventMessageTypeEnum::IMAGE) {
+        if event.action.as_deref() == Some("pull") {
+            history.pulled.insert(image_reference(&id), now);
+        }
+        return;
+    }
+    // Creating or removing containers of a compose project is part of deploying it
+    if matches!(event.action.as_deref(), Some("create" | "destroy"))
+        && !project.is_empty()
+        && !oneoff
+    {
+        history.deployed.insert(project.clone(), now);
+    }
+    match event.action.as_deref().unwrap_or("") {
+        // A stop, or a kill with a terminating signal, is deliberate; a crash only reports die
+        "stop" if !is_autoheal_stop(&id) => {
+            history.stopped.insert(id);
+        }
+        "kill"
+            if !is_autoheal_stop(&id)
+                && attributes
+                    .get("signal")
+                    .is_some_and(|s| matches!(s.as_str(), "9" | "15" | "SIGKILL" | "SIGTERM")) =>
+        {
+            history.stopped.insert(id);
+        }
+        "die" => {
+            history.died.insert(id);
+        }
+        "start" => {
+            history.stopped.remove(&id);
+            history.died.remove(&id);
+            forget_stop(&id);
+        }
+        // Recreated containers, e.g. by Watchtower, are known by name rather than id
+        "create" => {
+            let watchtower = attributes
+                .keys()
+                .any(|k| k.starts_with("com.centurylinklabs.watchtower"));
+            if let Some(name) = attributes.get("name") {
+                history.created.insert(name.clone(), (now, watchtower));
+            }
+        }
+        "destroy" => {
+            history.stopped.remove(&id);
+            history.died.remove(&id);
+            forget_stop(&id);
+            if !project.is_empty() && !oneoff {
+                history.removed.insert(project, now);
+            }
+        }
+        _ => (),
+    }
+    history
+        .removed
+        .retain(|_, r| r.elapsed() < COMPOSE_DOWN_WINDOW);
+}
+
+// Follow the Docker events stream in the background, reconnecting whenever it ends,
+// until the returned history is dropped
+pub fn start_events(docker: Docker, interval: u64) -> Arc<Mutex<EventHistoryList>> {
+    let history = Arc::new(Mutex::new(EventHistoryList::default()));
+    let shared = Arc::downgrade(&history);
+    let follower = BackgroundTask::spawn(async move {
+        loop {
+            let options = Some(EventsOptions::<String> {
+                filters: HashMap::from([(
+                    "type".to_string(),
+                    vec!["container".to_string(), "image".to_string()],
+                )]),
+                ..Default::default()
+            });
+            let mut stream = docker.events(options);
+            let reason = loop {
+                match stream.next().await {
+                    Some(Ok(event)) => match shared.upgrade() {
+                        Some(history) => record_event(&history, event),
+                        None => return,
+                    },
+                    Some(Err(e)) => break e.to_string(),
+                    None => break "stream ended".to_string(),
+                }
+            };
+            let msg0 = format!("Docker events are unavailable ({}); reconnecting", reason);
+            log_message(&msg0, WARNING).await;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    });
+    history.lock().unwrap().follower = Some(follower);
+    history
+}
+
+// Whether a container was stopped, on request or by autoheal, and has not been started since
+pub fn is_stopped(history: &Mutex<EventHistoryList>, id: &str) -> bool {
+    history
+        .lock()
+        .unwrap()
+        .stopped
+        .iter()
+        .any(|s| s.starts_with(id))
+        || autoheal_stops()
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|s| s.starts_with(id) || id.starts_with(s.as_str()))
+}
+
+// Whether a container was seen to die and has not been started since
+pub fn has_died(history: &Mutex<EventHistoryList>, id: &str) -> bool {
+    history
+        .lock()
+        .unwrap()
+        .died
+        .iter()
+        .any(|d| d.starts_with(id))
+}
+
+// Whether a compose project is being taken down, i.e. had a container removed within the window,
+// as with docker compose down; a single stopped member says nothing of the rest of the project
+pub fn is_stopping(history: &Mutex<EventHistoryList>, project: &str) -> bool {
+    if project.is_empty() {
+        return false;
+    }
+    history
+        .lock()
+        .unwrap()
+        .removed
+        .get(project)
+        .is_some_and(|r| r.elapsed() < COMPOSE_DOWN_WINDOW)
+}
+
+// Describe deploy activity affecting a container within the window, if any
+pub fn get_deploy(
+    history: &Mutex<EventHistoryList>,
+    name: &str,
+    project: &str,
+    image: &str,
+    window: u64,
+) -> Option<String> {
+    let window = Duration::from_secs(window);
+    let history = history.lock().unwrap();
+    if let Some((c, watchtower)) = history
+        .created
+        .get(name)
+        .filter(|(c, _)| c.elapsed() < window)
+    {
+        return Some(match watchtower {
+            true => format!(
+                "it was recreated by Watchtower {}s ago",
+                c.elapsed().as_secs()
+            ),
+            false => format!("it was created {}s ago", c.elapsed().as_secs()),
+        });
+    }
+    let image = image_reference(image);
+    if let Some(p) = history.pulled.get(&image).filter(|p| p.elapsed() < window) {
+        return Some(format!(
+            "image {} was pulled {}s ago",
+            image,
+            p.elapsed().as_secs()
+        ));
+    }
+    if let Some(d) = history
+        .deployed
+        .get(project)
+        .filter(|d| !project.is_empty() && d.elapsed() < window)
+    {
+        return Some(format!(
+            "compose project {} was deployed {}s ago",
+            project,
+            d.elapsed().as_secs()
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::models::EventActor;
+
+    fn event(action: &str, id: &str, project: &str) -> EventMessage {
+        EventMessage {
+            typ: Some(EventMessageTypeEnum::CONTAINER),
+            action: Some(action.to_string()),
+            actor: Some(EventActor {
+                id: Some(id.to_string()),
+                attributes: Some(HashMap::from([(
+                    "com.docker.compose.project".to_string(),
+                    project.to_string(),
+                )])),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stopped_member_does_not_take_project_down() {
+        let history = Mutex::new(EventHistoryList::default());
+        record_event(&history, event("stop", "aaaa1111", "web"));
+        assert!(is_stopped(&history, "aaaa"));
+        assert!(!is_stopping(&history, "web"));
+        record_event(&history, event("destroy", "aaaa1111", "web"));
+        assert!(!is_stopped(&history, "aaaa"));
+        assert!(is_stopping(&history, "web"));
+        assert!(!is_stopping(&history, "db"));
+    }
+
+    #[test]
+    fn oneoff_container_leaves_project_alone() {
+        let history = Mutex::new(EventHistoryList::default());
+        let mut oneoff = event("destroy", "dddd4444", "web");
+        if let Some(attributes) = oneoff.actor.as_mut().and_then(|a| a.attributes.as_mut()) {
+            attributes.insert("com.docker.compose.oneoff".to_string(), "True".to_string());
+        }
+        record_event(&history, oneoff);
+        assert!(!is_stopping(&history, "web"));
+        assert!(get_deploy(&history, "web-run-1", "web", "nginx", 60).is_none());
+    }
+
+    #[test]
+    fn autoheal_stop_is_not_a_request() {
+        let history = Mutex::new(EventHistoryList::default());
+        expect_stop("bbbb");
+        record_event(&history, event("stop", "bbbb2222", "web"));
+        assert!(history.lock().unwrap().stopped.is_empty());
+        // Still left stopped rather than resurrected, until started again
+        assert!(is_stopped(&history, "bbbb2222"));
+        record_event(&history, event("start", "bbbb2222", "web"));
+        assert!(!is_stopped(&history, "bbbb2222"));
+    }
+
+    #[test]
+    fn died_until_started_again() {
+        let history = Mutex::new(EventHistoryList::default());
+        assert!(!has_died(&history, "cccc"));
+        record_event(&history, event("die", "cccc3333", "web"));
+        assert!(has_died(&history, "cccc"));
+        record_event(&history, event("start", "cccc3333", "web"));
+        assert!(!has_died(&history, "cccc"));
+    }
+}
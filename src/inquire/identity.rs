@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+// Determine the stable identity used to key history across container re-creation
+pub fn get_identity(
+    identity_type: &str,
+    labels: &HashMap<String, String>,
+    name: &str,
+    id: &str,
+) -> String {
+    // A custom label always takes precedence
+    if let Some(identity) = labels.get("autoheal.identity") {
+        if !identity.is_empty() {
+            return identity.to_string();
+        }
+    }
+    match identity_type {
+        "id" => id.to_string(),
+        "compose" => match (
+            labels.get("com.docker.compose.project"),
+            labels.get("com.docker.compose.service"),
+        ) {
+            (Some(project), Some(service)) => format!("{}/{}", project, service),
+            _ => name.to_string(),
+        },
+        &_ => name.to_string(),
+    }
+}
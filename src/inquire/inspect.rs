@@ -1,14 +1,82 @@
-use crate::{log_message, ERROR};
-use bollard::Docker;
+use crate::{log_message, ERROR, WARNING};
+use bollard::{models::RestartPolicyNameEnum, Docker};
+use serde::{Deserialize, Serialize};
+
+// Longest healthcheck output kept per health log entry
+const OUTPUT_LIMIT: usize = 1024;
+
+// Health log entry as retained by Docker (by default the last 5)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HealthLogList {
+    pub start: String,
+    pub end: String,
+    pub exit_code: i64,
+    pub output: String,
+}
 
 pub struct Result {
     pub failed: bool,
     pub failing_streak: i64,
     pub failing_reason: String,
+    pub output: String,
     pub exit_code: i64,
+    pub hostname: String,
+    pub health_log: Vec<HealthLogList>,
+}
+
+impl Result {
+    // A failure detected by autoheal itself (log pattern, resource limit or probe)
+    pub fn triggered(reason: String, failing_streak: i64) -> Self {
+        Result {
+            failed: true,
+            failing_streak,
+            failing_reason: reason.chars().flat_map(char::escape_default).collect(),
+            output: reason,
+            exit_code: -1,
+            hostname: "".to_string(),
+            health_log: vec![],
+        }
+    }
+}
+
+// Summarise failures of the health log, e.g. 3 of last 5 failed: timeout, timeout, refused
+pub fn summarise_health_log(health_log: &[HealthLogList]) -> String {
+    let failures: Vec<String> = health_log
+        .iter()
+        .filter(|h| h.exit_code != 0)
+        .map(|h| {
+            let line = h.output.lines().next().unwrap_or("").trim();
+            match line.chars().count() > 40 {
+                true => format!("{}...", line.chars().take(40).collect::<String>()),
+                false => line.to_string(),
+            }
+        })
+        .collect();
+    format!(
+        "{} of last {} failed: {}",
+        failures.len(),
+        health_log.len(),
+        failures.join(", ")
+    )
 }
 
-pub async fn inspect_container(docker: Docker, name: &str, id: &str) -> Result {
+// Whether the restart policy of a container is no (or unset), i.e. it is not meant to be restarted
+pub async fn has_no_restart_policy(docker: &Docker, id: &str) -> bool {
+    let policy = match docker.inspect_container(id, None).await {
+        Ok(i) => i
+            .host_config
+            .and_then(|h| h.restart_policy)
+            .and_then(|r| r.name)
+            .unwrap_or(RestartPolicyNameEnum::EMPTY),
+        Err(_) => return false,
+    };
+    matches!(
+        policy,
+        RestartPolicyNameEnum::EMPTY | RestartPolicyNameEnum::NO
+    )
+}
+
+pub async fn inspect_container(docker: Docker, name: &str, id: &str, runtime: &str) -> Result {
     // Attempt to inspect the container
     let container_inspect = match docker.inspect_container(id, None).await {
         Ok(response) => response,
@@ -30,6 +98,16 @@ pub async fn inspect_container(docker: Docker, name: &str, id: &str) -> Result {
         .and_then(|s| s.health.as_ref().and_then(|h| h.failing_streak))
     {
         Some(streak) => streak,
+        // Older Podman releases report health as State.Healthcheck, which is not exposed here;
+        // the container was listed as unhealthy, so treat it as failing
+        None if runtime == "podman" => {
+            let msg0 = format!(
+                "[{} ({})] Podman did not report container failing streak; default to 1",
+                name, id
+            );
+            log_message(&msg0, WARNING).await;
+            1
+        }
         None => {
             // Log that we had an error
             let msg0 = format!(
@@ -41,15 +119,40 @@ pub async fn inspect_container(docker: Docker, name: &str, id: &str) -> Result {
             0
         }
     };
+    // Podman sets the hostname of pod members to the pod name
+    let hostname = container_inspect
+        .config
+        .as_ref()
+        .and_then(|c| c.hostname.clone())
+        .unwrap_or_default();
     // Get last 'output' and 'exitcode' from state:health
     let mut failing_reason = "unknown".to_string();
     let mut exit_code: i64 = -1;
+    let mut output = "".to_string();
+    let mut health_log = vec![];
     if let Some(log) = container_inspect
         .state
         .as_ref()
         .and_then(|s| s.health.as_ref().and_then(|h| h.log.clone()))
     {
+        // Keep every retained entry, un-escaped and within limits
+        health_log = log
+            .iter()
+            .map(|h| HealthLogList {
+                start: h.start.as_ref().map(|d| d.to_string()).unwrap_or_default(),
+                end: h.end.as_ref().map(|d| d.to_string()).unwrap_or_default(),
+                exit_code: h.exit_code.unwrap_or(-1),
+                output: h
+                    .output
+                    .as_deref()
+                    .unwrap_or("")
+                    .chars()
+                    .take(OUTPUT_LIMIT)
+                    .collect(),
+            })
+            .collect();
         if let Some(last) = log.last() {
+            output = last.output.clone().unwrap_or_default();
             // Normalize failing reason
             failing_reason = last
                 .output
@@ -73,6 +176,9 @@ pub async fn inspect_container(docker: Docker, name: &str, id: &str) -> Result {
         failed: failing_streak != 0,
         failing_streak,
         failing_reason,
+        output,
         exit_code,
+        hostname,
+        health_log,
     }
 }
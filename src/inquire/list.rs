@@ -2,11 +2,15 @@ use crate::{report::logging::log_message, ERROR};
 use bollard::{container::ListContainersOptions, models::ContainerSummary, Docker};
 use std::collections::HashMap;
 
-pub async fn containers_list(docker: Docker) -> Vec<ContainerSummary> {
+pub async fn containers_list(docker: Docker, runtime: &str) -> Vec<ContainerSummary> {
     // Build container assessment criteria
     let mut filters = HashMap::new();
     filters.insert("health", vec!["unhealthy"]);
-    filters.insert("status", vec!["running", "dead"]);
+    // Podman has no dead state and rejects it as a filter value
+    match runtime {
+        "podman" => filters.insert("status", vec!["running"]),
+        _ => filters.insert("status", vec!["running", "dead"]),
+    };
 
     // Gather all containers that are unhealthy
     let container_options = Some(ListContainersOptions {
@@ -0,0 +1,28 @@
+use bollard::{container::LogsOptions, Docker};
+use futures::StreamExt;
+
+// Fetch the last lines of stdout and stderr, keeping at most the last `bytes` bytes
+pub async fn get_log_tail(docker: &Docker, id: &str, lines: u64, bytes: u64) -> Vec<String> {
+    if lines == 0 {
+        return vec![];
+    }
+    let options = Some(LogsOptions {
+        stdout: true,
+        stderr: true,
+        timestamps: true,
+        tail: lines.to_string(),
+        ..Default::default()
+    });
+    let mut stream = docker.logs(id, options);
+    let mut output = String::new();
+    while let Some(Ok(chunk)) = stream.next().await {
+        output.push_str(&chunk.to_string());
+    }
+    let mut tail: Vec<String> = output.lines().map(|l| l.to_string()).collect();
+    // Drop the oldest lines beyond the byte limit
+    let mut total: u64 = tail.iter().map(|l| l.len() as u64 + 1).sum();
+    while bytes > 0 && total > bytes && !tail.is_empty() {
+        total -= tail.remove(0).len() as u64 + 1;
+    }
+    tail
+}
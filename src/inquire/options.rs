@@ -1,4 +1,8 @@
-use crate::{report::logging::print_version, ALLOWED_CONNECTION_TYPES};
+use crate::{
+    report::logging::print_version, ALLOWED_CONNECTION_TYPES, ALLOWED_HISTORY_FORMATS,
+    ALLOWED_HISTORY_OUTCOMES, ALLOWED_IDENTITY_TYPES, ALLOWED_POD_RESTARTS, ALLOWED_RESTART_SCOPES,
+    ALLOWED_RUNTIMES, ALLOWED_SWARM_ACTIONS,
+};
 use getopts::Options;
 
 pub struct OptionsList {
@@ -17,6 +21,44 @@ pub struct OptionsList {
     pub webhook_url: Option<String>,
     pub log_persist: bool,
     pub post_action: Option<String>,
+    pub log_max_age: Option<String>,
+    pub log_max_size: Option<String>,
+    pub log_max_files: Option<String>,
+    pub log_max_artifacts: Option<String>,
+    pub log_compress: bool,
+    pub log_quarantine: bool,
+    pub log_path: Option<String>,
+    pub identity: Option<String>,
+    pub hosts_file: Option<String>,
+    pub ssh_host: Option<String>,
+    pub ssh_key: Option<String>,
+    pub ssh_known_hosts: Option<String>,
+    pub socket_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    pub tls_cert_path: Option<String>,
+    pub tls_ca_path: Option<String>,
+    pub runtime: Option<String>,
+    pub pod_restart: Option<String>,
+    pub swarm_action: Option<String>,
+    pub restart_scope: Option<String>,
+    pub monitor_exited: bool,
+    pub exited_budget: Option<String>,
+    pub failing_threshold: Option<String>,
+    pub unhealthy_duration: Option<String>,
+    pub log_debug: bool,
+    pub ignore_output: Option<String>,
+    pub log_tail: Option<String>,
+    pub log_tail_bytes: Option<String>,
+    pub log_tail_notify: bool,
+    pub diagnostics: bool,
+    pub diagnostics_export: bool,
+    pub log_pattern: Option<String>,
+    pub log_pattern_threshold: Option<String>,
+    pub log_pattern_window: Option<String>,
+    pub skip_restart_no: bool,
+    pub deploy_window: Option<String>,
+    pub maintenance: Option<String>,
+    pub quiet_hours: Option<String>,
 }
 
 pub fn get_opts(args: Vec<String>) -> OptionsList {
@@ -28,7 +70,7 @@ pub fn get_opts(args: Vec<String>) -> OptionsList {
     opts.optopt(
         "c",
         "connection-type",
-        "One of local, socket, http, or ssl",
+        "One of local, socket, http, ssl, or ssh",
         "<CONNECTION_TYPE>",
     );
     opts.optopt(
@@ -103,6 +145,222 @@ pub fn get_opts(args: Vec<String>) -> OptionsList {
         "<SCRIPT_PATH>",
     );
     opts.optflag("V", "version", "Print version information");
+    opts.optopt(
+        "",
+        "log-path",
+        "The absolute path to the directory for persistent log data",
+        "<LOG_PATH>",
+    );
+    opts.optopt(
+        "",
+        "log-max-age",
+        "Time in days to retain persistent log records before compacting them",
+        "<LOG_MAX_AGE>",
+    );
+    opts.optopt(
+        "",
+        "log-max-size",
+        "Size in megabytes at which the persistent log is rotated",
+        "<LOG_MAX_SIZE>",
+    );
+    opts.optopt(
+        "",
+        "log-max-files",
+        "Number of rotated persistent log files to keep",
+        "<LOG_MAX_FILES>",
+    );
+    opts.optopt(
+        "",
+        "log-max-artifacts",
+        "Number of incident log files and diagnostics bundles to keep",
+        "<LOG_MAX_ARTIFACTS>",
+    );
+    opts.optflag(
+        "",
+        "log-compress",
+        "Enable gzip compression of rotated log files",
+    );
+    opts.optopt(
+        "",
+        "socket-path",
+        "The absolute path to the Docker unix socket or named pipe (when -c local or socket)",
+        "<SOCKET_PATH>",
+    );
+    opts.optopt(
+        "",
+        "tls-key-path",
+        "The absolute path to the ssl private key file; overrides key.pem in -k (when -c ssl)",
+        "<TLS_KEY_PATH>",
+    );
+    opts.optopt(
+        "",
+        "tls-cert-path",
+        "The absolute path to the ssl certificate file; overrides cert.pem in -k (when -c ssl)",
+        "<TLS_CERT_PATH>",
+    );
+    opts.optopt(
+        "",
+        "tls-ca-path",
+        "The absolute path to the ssl CA certificate file; overrides ca.pem in -k (when -c ssl)",
+        "<TLS_CA_PATH>",
+    );
+    opts.optopt(
+        "",
+        "ssh-host",
+        "The ssh destination of the Docker host, e.g. ssh://user@host:22 (when -c ssh)",
+        "<SSH_HOST>",
+    );
+    opts.optopt(
+        "",
+        "ssh-key",
+        "The absolute path to the ssh private key (when -c ssh)",
+        "<SSH_KEY>",
+    );
+    opts.optopt(
+        "",
+        "ssh-known-hosts",
+        "The absolute path to the ssh known_hosts file used to verify the Docker host (when -c ssh)",
+        "<SSH_KNOWN_HOSTS>",
+    );
+    opts.optopt(
+        "",
+        "hosts-file",
+        "The absolute path to a JSON file listing Docker hosts to monitor concurrently",
+        "<HOSTS_FILE>",
+    );
+    opts.optopt(
+        "",
+        "identity",
+        "One of name, id, or compose; how containers are identified in persistent log data",
+        "<IDENTITY>",
+    );
+    opts.optflag(
+        "",
+        "log-quarantine",
+        "Enable moving corrupt persistent log lines to a quarantine file",
+    );
+    opts.optopt(
+        "",
+        "runtime",
+        "One of auto, docker, or podman; the container engine behind the API",
+        "<RUNTIME>",
+    );
+    opts.optopt(
+        "",
+        "pod-restart",
+        "One of container or pod; what to restart when a Podman pod member is unhealthy",
+        "<POD_RESTART>",
+    );
+    opts.optopt(
+        "",
+        "swarm-action",
+        "One of restart, update, or remove; how an unhealthy Swarm task is remediated",
+        "<SWARM_ACTION>",
+    );
+    opts.optopt(
+        "",
+        "restart-scope",
+        "One of container, dependents, or project; what to restart when a compose service is unhealthy",
+        "<RESTART_SCOPE>",
+    );
+    opts.optflag(
+        "",
+        "monitor-exited",
+        "Enable restarting containers that exit unexpectedly, with a non-zero code or OOM-killed",
+    );
+    opts.optopt(
+        "",
+        "exited-budget",
+        "Number of restarts per hour of an exited container before giving up (0 is unlimited)",
+        "<EXITED_BUDGET>",
+    );
+    opts.optopt(
+        "",
+        "failing-threshold",
+        "Number of consecutive healthcheck failures before acting on an unhealthy container",
+        "<FAILING_THRESHOLD>",
+    );
+    opts.optopt(
+        "",
+        "unhealthy-duration",
+        "Time in seconds a container must be unhealthy before acting on it",
+        "<UNHEALTHY_DURATION>",
+    );
+    opts.optflag("", "log-debug", "Enable debug logging");
+    opts.optopt(
+        "",
+        "ignore-output",
+        "A regular expression of healthcheck output for which remediation is skipped",
+        "<IGNORE_OUTPUT>",
+    );
+    opts.optopt(
+        "",
+        "log-tail",
+        "Number of container log lines to capture before acting (0 disables)",
+        "<LOG_TAIL>",
+    );
+    opts.optopt(
+        "",
+        "log-tail-bytes",
+        "Size in bytes to which captured container log lines are limited",
+        "<LOG_TAIL_BYTES>",
+    );
+    opts.optflag(
+        "",
+        "log-tail-notify",
+        "Enable including captured container log lines in notifications",
+    );
+    opts.optflag(
+        "",
+        "diagnostics",
+        "Enable capturing a diagnostics bundle before acting",
+    );
+    opts.optflag(
+        "",
+        "diagnostics-export",
+        "Enable including an export of the container filesystem in diagnostics bundles",
+    );
+    opts.optopt(
+        "",
+        "log-pattern",
+        "A regular expression of container log output for which monitored containers are remediated",
+        "<LOG_PATTERN>",
+    );
+    opts.optopt(
+        "",
+        "log-pattern-threshold",
+        "Number of log pattern matches within the window before acting",
+        "<LOG_PATTERN_THRESHOLD>",
+    );
+    opts.optopt(
+        "",
+        "log-pattern-window",
+        "Time in seconds within which log pattern matches are counted",
+        "<LOG_PATTERN_WINDOW>",
+    );
+    opts.optflag(
+        "",
+        "skip-restart-no",
+        "Skip remediation of unhealthy containers whose restart policy is no",
+    );
+    opts.optopt(
+        "",
+        "deploy-window",
+        "Time in seconds remediation is suppressed after deploy activity (0 disables)",
+        "<DEPLOY_WINDOW>",
+    );
+    opts.optopt(
+        "",
+        "maintenance",
+        "Windows in which remediation is skipped, e.g. \"0 2 * * * 2h\"",
+        "<MAINTENANCE>",
+    );
+    opts.optopt(
+        "",
+        "quiet-hours",
+        "Windows in which only notifications of failed remediation are sent",
+        "<QUIET_HOURS>",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -119,6 +377,8 @@ pub fn get_opts(args: Vec<String>) -> OptionsList {
         std::process::exit(0);
     } else if matches.opt_present("h") {
         println!("{}", opts.usage(&program));
+        println!("Subcommands:");
+        println!("    history             Query persistent log data (see history --help)");
         std::process::exit(0);
     }
 
@@ -135,6 +395,45 @@ pub fn get_opts(args: Vec<String>) -> OptionsList {
         }
     }
 
+    // Ensure acceptable identity arguments
+    if let Some(identity) = matches.opt_str("identity") {
+        if !ALLOWED_IDENTITY_TYPES.contains(&identity.as_str()) {
+            println!("Unexpected identity: {}", identity);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+
+    // Ensure acceptable runtime arguments
+    if let Some(runtime) = matches.opt_str("runtime") {
+        if !ALLOWED_RUNTIMES.contains(&runtime.as_str()) {
+            println!("Unexpected runtime: {}", runtime);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+    if let Some(pod_restart) = matches.opt_str("pod-restart") {
+        if !ALLOWED_POD_RESTARTS.contains(&pod_restart.as_str()) {
+            println!("Unexpected pod-restart: {}", pod_restart);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+    if let Some(swarm_action) = matches.opt_str("swarm-action") {
+        if !ALLOWED_SWARM_ACTIONS.contains(&swarm_action.as_str()) {
+            println!("Unexpected swarm-action: {}", swarm_action);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+    if let Some(restart_scope) = matches.opt_str("restart-scope") {
+        if !ALLOWED_RESTART_SCOPES.contains(&restart_scope.as_str()) {
+            println!("Unexpected restart-scope: {}", restart_scope);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+
     OptionsList {
         apprise_url: matches.opt_str("a"),
         connection_type: matches.opt_str("c"),
@@ -151,5 +450,130 @@ pub fn get_opts(args: Vec<String>) -> OptionsList {
         webhook_url: matches.opt_str("w"),
         log_persist: matches.opt_present("L"),
         post_action: matches.opt_str("P"),
+        log_max_age: matches.opt_str("log-max-age"),
+        log_max_size: matches.opt_str("log-max-size"),
+        log_max_files: matches.opt_str("log-max-files"),
+        log_max_artifacts: matches.opt_str("log-max-artifacts"),
+        log_compress: matches.opt_present("log-compress"),
+        log_quarantine: matches.opt_present("log-quarantine"),
+        log_path: matches.opt_str("log-path"),
+        identity: matches.opt_str("identity"),
+        hosts_file: matches.opt_str("hosts-file"),
+        ssh_host: matches.opt_str("ssh-host"),
+        ssh_key: matches.opt_str("ssh-key"),
+        ssh_known_hosts: matches.opt_str("ssh-known-hosts"),
+        socket_path: matches.opt_str("socket-path"),
+        tls_key_path: matches.opt_str("tls-key-path"),
+        tls_cert_path: matches.opt_str("tls-cert-path"),
+        tls_ca_path: matches.opt_str("tls-ca-path"),
+        runtime: matches.opt_str("runtime"),
+        pod_restart: matches.opt_str("pod-restart"),
+        swarm_action: matches.opt_str("swarm-action"),
+        restart_scope: matches.opt_str("restart-scope"),
+        monitor_exited: matches.opt_present("monitor-exited"),
+        exited_budget: matches.opt_str("exited-budget"),
+        failing_threshold: matches.opt_str("failing-threshold"),
+        unhealthy_duration: matches.opt_str("unhealthy-duration"),
+        log_debug: matches.opt_present("log-debug"),
+        ignore_output: matches.opt_str("ignore-output"),
+        log_tail: matches.opt_str("log-tail"),
+        log_tail_bytes: matches.opt_str("log-tail-bytes"),
+        log_tail_notify: matches.opt_present("log-tail-notify"),
+        diagnostics: matches.opt_present("diagnostics"),
+        diagnostics_export: matches.opt_present("diagnostics-export"),
+        log_pattern: matches.opt_str("log-pattern"),
+        log_pattern_threshold: matches.opt_str("log-pattern-threshold"),
+        log_pattern_window: matches.opt_str("log-pattern-window"),
+        skip_restart_no: matches.opt_present("skip-restart-no"),
+        deploy_window: matches.opt_str("deploy-window"),
+        maintenance: matches.opt_str("maintenance"),
+        quiet_hours: matches.opt_str("quiet-hours"),
+    }
+}
+
+pub struct HistoryOptionsList {
+    pub container: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub outcome: Option<String>,
+    pub format: String,
+    pub log_path: Option<String>,
+}
+
+pub fn get_history_opts(args: Vec<String>) -> HistoryOptionsList {
+    let program = format!("{} history", args[0]);
+
+    // Establish usable arguments
+    let mut opts = Options::new();
+    opts.optopt(
+        "c",
+        "container",
+        "Only include records for the container with this name or id",
+        "<CONTAINER>",
+    );
+    opts.optopt("f", "format", "One of table or json", "<FORMAT>");
+    opts.optflag("h", "help", "Print help");
+    opts.optopt(
+        "o",
+        "outcome",
+        "One of success, failed, disabled, or other",
+        "<OUTCOME>",
+    );
+    opts.optopt(
+        "s",
+        "since",
+        "Only include records from this date/time (YYYY-MM-DD[ HH:MM:SS]) or age (e.g. 7d, 12h, 30m)",
+        "<SINCE>",
+    );
+    opts.optopt(
+        "u",
+        "until",
+        "Only include records up to this date/time (YYYY-MM-DD[ HH:MM:SS]) or age (e.g. 7d, 12h, 30m)",
+        "<UNTIL>",
+    );
+    opts.optopt(
+        "",
+        "log-path",
+        "The absolute path to the directory for persistent log data",
+        "<LOG_PATH>",
+    );
+
+    let matches = match opts.parse(&args[2..]) {
+        Ok(m) => m,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    };
+
+    // Process matching arguments
+    if matches.opt_present("h") {
+        println!("{}", opts.usage(&program));
+        std::process::exit(0);
+    }
+
+    // Ensure acceptable format and outcome arguments
+    let format = matches.opt_str("f").unwrap_or("table".to_string());
+    if !ALLOWED_HISTORY_FORMATS.contains(&format.as_str()) {
+        println!("Unexpected format: {}", format);
+        println!("{}", opts.usage(&program));
+        std::process::exit(1);
+    }
+    if let Some(outcome) = matches.opt_str("o") {
+        if !ALLOWED_HISTORY_OUTCOMES.contains(&outcome.as_str()) {
+            println!("Unexpected outcome: {}", outcome);
+            println!("{}", opts.usage(&program));
+            std::process::exit(1);
+        }
+    }
+
+    HistoryOptionsList {
+        container: matches.opt_str("c"),
+        since: matches.opt_str("s"),
+        until: matches.opt_str("u"),
+        outcome: matches.opt_str("o"),
+        format,
+        log_path: matches.opt_str("log-path"),
     }
 }
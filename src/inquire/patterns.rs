@@ -0,0 +1,296 @@
+use super::{background::BackgroundTask, inspect};
+use crate::{log_message, WARNING};
+use bollard::{
+    container::{ListContainersOptions, LogOutput, LogsOptions},
+    models::ContainerSummary,
+    Docker,
+};
+use futures::StreamExt;
+use regex::Regex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+// Longest partial line kept while waiting for the rest of it
+const MAX_PARTIAL: usize = 64 * 1024;
+
+// Log pattern configuration applied to monitored containers without their own labels
+#[derive(Clone)]
+pub struct PatternDefaultsList {
+    pub pattern: String,
+    pub threshold: usize,
+    pub window: u64,
+    pub monitor_all: bool,
+}
+
+// Log followers per container id, and containers whose log matched often enough
+// Followers of containers with an invalid pattern are recorded as None so they are reported once
+#[derive(Default)]
+pub struct PatternHistoryList {
+    updater: Option<BackgroundTask>,
+    followers: HashMap<String, Option<BackgroundTask>>,
+    triggered: Vec<(ContainerSummary, inspect::Result)>,
+}
+
+// Pattern declared for a container
+struct PatternList {
+    regex: Regex,
+    threshold: usize,
+    window: Duration,
+}
+
+// Determine the pattern of a container, from its labels or the defaults where it is monitored
+fn get_pattern(
+    container: &ContainerSummary,
+    defaults: &PatternDefaultsList,
+) -> Option<(String, usize, u64)> {
+    let labels = container.labels.clone().unwrap_or_default();
+    let pattern = match labels.get("autoheal.log.pattern") {
+        Some(p) => p.clone(),
+        None => {
+            let monitored = match labels.get("autoheal.monitor.enable") {
+                Some(v) => v == "true",
+                None => defaults.monitor_all,
+            };
+            match monitored {
+                true => defaults.pattern.clone(),
+                false => "".to_string(),
+            }
+        }
+    };
+    if pattern.is_empty() {
+        return None;
+    }
+    let threshold = labels
+        .get("autoheal.log.pattern.threshold")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(defaults.threshold)
+        .max(1);
+    let window = labels
+        .get("autoheal.log.pattern.window")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(defaults.window);
+    Some((pattern, threshold, window))
+}
+
+// Append a chunk to the buffer of its stream, returning the complete lines and keeping a partial
+// last line for the next chunk
+fn take_lines(buffer: &mut Vec<u8>, chunk: &[u8]) -> Vec<String> {
+    buffer.extend_from_slice(chunk);
+    let complete = match buffer.iter().rposition(|b| *b == b'\n') {
+        Some(i) => i + 1,
+        // Overlong lines are matched as they are rather than buffered without bound
+        None if buffer.len() > MAX_PARTIAL => buffer.len(),
+        None => return vec![],
+    };
+    let rest = buffer.split_off(complete);
+    let lines = std::mem::replace(buffer, rest);
+    String::from_utf8_lossy(&lines)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+// Follow the log of a container from now on, recording it once the pattern matches often enough
+async fn follow_log(
+    docker: Docker,
+    container: ContainerSummary,
+    id: String,
+    pattern: PatternList,
+    history: Weak<Mutex<PatternHistoryList>>,
+) {
+    let options = Some(LogsOptions::<String> {
+        follow: true,
+        stdout: true,
+        stderr: true,
+        since: chrono::Utc::now().timestamp(),
+        ..Default::default()
+    });
+    let mut stream = docker.logs(&id, options);
+    let mut matches: VecDeque<Instant> = VecDeque::new();
+    // Partial lines of stdout and stderr, which arrive interleaved
+    let mut buffers: [Vec<u8>; 2] = Default::default();
+    let mut following = true;
+    while following {
+        let lines = match stream.next().await {
+            Some(Ok(chunk)) => {
+                let stderr = matches!(chunk, LogOutput::StdErr { .. });
+                take_lines(&mut buffers[stderr as usize], chunk.as_ref())
+            }
+            // The stream ends when the container stops, after which it is followed anew
+            _ => {
+                following = false;
+                buffers
+                    .iter_mut()
+                    .filter(|b| !b.is_empty())
+                    .map(|b| String::from_utf8_lossy(&std::mem::take(b)).to_string())
+                    .collect()
+            }
+        };
+        for line in lines.iter().filter(|l| pattern.regex.is_match(l)) {
+            let now = Instant::now();
+            matches.push_back(now);
+            while matches
+                .front()
+                .is_some_and(|m| now.duration_since(*m) > pattern.window)
+            {
+                matches.pop_front();
+            }
+            if matches.len() < pattern.threshold {
+                continue;
+            }
+            // Count matches afresh once the container has been restarted
+            let reason = format!(
+                "Log matched {} {} times within {}s: {}",
+                pattern.regex.as_str(),
+                matches.len(),
+                pattern.window.as_secs(),
+                line.trim()
+            );
+            matches.clear();
+            let Some(history) = history.upgrade() else {
+                return;
+            };
+            history
+                .lock()
+                .unwrap()
+                .triggered
+                .push((container.clone(), inspect::Result::triggered(reason, 1)));
+        }
+    }
+}
+
+// Start and stop log followers as containers come and go
+async fn update_followers(
+    docker: &Docker,
+    defaults: &PatternDefaultsList,
+    history: &Arc<Mutex<PatternHistoryList>>,
+) {
+    let containers = match docker
+        .list_containers(None::<ListContainersOptions<String>>)
+        .await
+    {
+        Ok(list) => list,
+        Err(_) => return,
+    };
+    let mut wanted = vec![];
+    for container in containers {
+        if let Some(pattern) = get_pattern(&container, defaults) {
+            wanted.push((container, pattern));
+        }
+    }
+    let mut invalid = vec![];
+    {
+        let mut state = history.lock().unwrap();
+        // Stop following containers that are gone, and forget followers whose stream ended
+        state.followers.retain(|id, handle| {
+            wanted.iter().any(|(c, _)| c.id.as_ref() == Some(id))
+                && handle.as_ref().is_none_or(|h| !h.is_finished())
+        });
+        for (container, (pattern, threshold, window)) in wanted {
+            let id = container.id.clone().unwrap_or_default();
+            if state.followers.contains_key(&id) {
+                continue;
+            }
+            let handle = match Regex::new(&pattern) {
+                Ok(regex) => {
+                    let pattern = PatternList {
+                        regex,
+                        threshold,
+                        window: Duration::from_secs(window),
+                    };
+                    Some(BackgroundTask::spawn(follow_log(
+                        docker.clone(),
+                        container.clone(),
+                        id.clone(),
+                        pattern,
+                        Arc::downgrade(history),
+                    )))
+                }
+                Err(e) => {
+                    let name = container
+                        .names
+                        .as_ref()
+                        .and_then(|n| n.first())
+                        .map(|n| n.trim_matches('/').to_string())
+                        .unwrap_or_default();
+                    invalid.push(format!(
+                        "[{} ({})] Invalid pattern ({}): {}",
+                        name,
+                        id.chars().take(12).collect::<String>(),
+                        pattern,
+                        e
+                    ));
+                    None
+                }
+            };
+            state.followers.insert(id, handle);
+        }
+    }
+    for msg0 in invalid {
+        log_message(&msg0, WARNING).await;
+    }
+}
+
+// Follow container logs in the background, keeping followers in step with running containers
+// until the returned history is dropped
+pub fn start_patterns(
+    docker: Docker,
+    defaults: PatternDefaultsList,
+    interval: u64,
+) -> Arc<Mutex<PatternHistoryList>> {
+    let history = Arc::new(Mutex::new(PatternHistoryList::default()));
+    let shared = Arc::downgrade(&history);
+    let updater = BackgroundTask::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            interval.tick().await;
+            let Some(history) = shared.upgrade() else {
+                break;
+            };
+            update_followers(&docker, &defaults, &history).await;
+        }
+    });
+    history.lock().unwrap().updater = Some(updater);
+    history
+}
+
+// Containers whose log matched their pattern often enough since the last call
+pub fn take_matched(
+    history: &Mutex<PatternHistoryList>,
+) -> Vec<(ContainerSummary, inspect::Result)> {
+    std::mem::take(&mut history.lock().unwrap().triggered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_lines_keeps_partial_line() {
+        let mut buffer = vec![];
+        assert!(take_lines(&mut buffer, b"connection pool ex").is_empty());
+        assert_eq!(
+            take_lines(&mut buffer, b"hausted\nnext li"),
+            vec!["connection pool exhausted"]
+        );
+        assert_eq!(
+            take_lines(&mut buffer, b"ne\r\nlast\n"),
+            vec!["next line", "last"]
+        );
+        assert!(buffer.is_empty());
+        // Characters split across chunks are decoded once complete
+        assert!(take_lines(&mut buffer, &"é\n".as_bytes()[..1]).is_empty());
+        assert_eq!(take_lines(&mut buffer, &"é\n".as_bytes()[1..]), vec!["é"]);
+    }
+
+    #[test]
+    fn take_lines_bounds_partial_line() {
+        let mut buffer = vec![];
+        let lines = take_lines(&mut buffer, &vec![b'x'; MAX_PARTIAL + 1]);
+        assert_eq!(lines.len(), 1);
+        assert!(buffer.is_empty());
+    }
+}
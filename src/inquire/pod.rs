@@ -0,0 +1,51 @@
+use bollard::{container::ListContainersOptions, Docker};
+use std::collections::HashMap;
+
+// Gather the containers of a Podman pod, excluding its infra container
+pub async fn pod_members(docker: &Docker, pod: &str) -> Vec<(String, String)> {
+    let mut filters = HashMap::new();
+    filters.insert("pod", vec![pod]);
+    let container_options = Some(ListContainersOptions {
+        all: true,
+        filters,
+        ..Default::default()
+    });
+    match docker.list_containers(container_options).await {
+        Ok(list) => list
+            .into_iter()
+            .filter_map(|c| {
+                let name = c.names?.first()?.trim_matches('/').to_string();
+                let id: String = c.id?.chars().take(12).collect();
+                Some((name, id))
+            })
+            .filter(|(name, _)| !name.ends_with("-infra"))
+            .collect(),
+        // Podman reports an unknown pod as an error
+        Err(_) => vec![],
+    }
+}
+
+// Determine the Podman pod of a container from the autoheal.pod label or its hostname
+pub async fn get_pod(
+    docker: &Docker,
+    labels: &HashMap<String, String>,
+    hostname: &str,
+    id: &str,
+) -> String {
+    let candidate = match labels.get("autoheal.pod") {
+        Some(p) if !p.is_empty() => p.clone(),
+        _ => hostname.to_string(),
+    };
+    if candidate.is_empty() {
+        return "".to_string();
+    }
+    // Confirm membership, as the hostname may have been set independently of a pod
+    match pod_members(docker, &candidate)
+        .await
+        .iter()
+        .any(|(_, i)| i == id)
+    {
+        true => candidate,
+        false => "".to_string(),
+    }
+}
@@ -0,0 +1,320 @@
+use super::inspect;
+use crate::{log_message, WARNING};
+use bollard::{
+    container::ListContainersOptions,
+    exec::{CreateExecOptions, StartExecResults},
+    models::ContainerSummary,
+    Docker,
+};
+use futures::StreamExt;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+use tokio::net::TcpStream;
+
+// Probe results of a container carried between cycles
+struct ProbeStateList {
+    last: Instant,
+    failures: i64,
+}
+
+// Probe state per container id, and containers whose probe cannot run, so they are reported once
+#[derive(Default)]
+pub struct ProbeHistoryList {
+    state: HashMap<String, ProbeStateList>,
+    skipped: HashSet<String>,
+}
+
+// Probe declared by labels on a container
+struct ProbeList {
+    container: ContainerSummary,
+    id: String,
+    kind: String,
+    target: String,
+    address: Option<String>,
+    timeout: Duration,
+}
+
+// Address of the container on the requested network, or the first network with an address
+fn get_address(container: &ContainerSummary, network: Option<&String>) -> Option<String> {
+    let networks = container
+        .network_settings
+        .as_ref()
+        .and_then(|n| n.networks.clone())
+        .unwrap_or_default();
+    let mut addresses: Vec<(String, String)> = networks
+        .into_iter()
+        .filter_map(|(name, n)| Some((name, n.ip_address?)))
+        .filter(|(name, ip)| !ip.is_empty() && network.is_none_or(|n| n == name))
+        .collect();
+    addresses.sort();
+    // Host networked containers have no address of their own
+    addresses.first().map(|(_, ip)| ip.clone())
+}
+
+// Whether a probe target leaves the host to the container address, e.g. http://:8080/health or 5432
+fn needs_address(kind: &str, target: &str) -> bool {
+    match kind {
+        "http" => target.contains("://:") || target.contains(":///"),
+        "tcp" => !target.contains(':'),
+        _ => false,
+    }
+}
+
+async fn probe_http(target: &str, address: &str, timeout: Duration) -> Result<(), String> {
+    // Fill in the container address where the host is omitted, e.g. http://:8080/health
+    let url = target
+        .replacen("://:", &format!("://{}:", address), 1)
+        .replacen(":///", &format!("://{}/", address), 1);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    match client.get(&url).send().await {
+        Ok(r) if r.status().is_success() || r.status().is_redirection() => Ok(()),
+        Ok(r) => Err(format!("GET {} returned {}", url, r.status())),
+        Err(e) => Err(format!("GET {} failed: {}", url, e)),
+    }
+}
+
+async fn probe_tcp(target: &str, address: &str, timeout: Duration) -> Result<(), String> {
+    let destination = match target.contains(':') {
+        true => target.to_string(),
+        false => format!("{}:{}", address, target),
+    };
+    match tokio::time::timeout(timeout, TcpStream::connect(&destination)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("Connection to {} failed: {}", destination, e)),
+        Err(_) => Err(format!("Connection to {} timed out", destination)),
+    }
+}
+
+async fn probe_exec(
+    docker: &Docker,
+    id: &str,
+    command: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let config = CreateExecOptions {
+        cmd: Some(vec!["sh", "-c", command]),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        ..Default::default()
+    };
+    let exec = docker
+        .create_exec(id, config)
+        .await
+        .map_err(|e| format!("Could not create exec: {}", e))?;
+    let run = async {
+        let mut output = String::new();
+        if let StartExecResults::Attached {
+            output: mut stream, ..
+        } = docker
+            .start_exec(&exec.id, None)
+            .await
+            .map_err(|e| format!("Could not start exec: {}", e))?
+        {
+            while let Some(Ok(chunk)) = stream.next().await {
+                output.push_str(&chunk.to_string());
+            }
+        }
+        Ok::<String, String>(output)
+    };
+    let output = match tokio::time::timeout(timeout, run).await {
+        Ok(o) => o?,
+        Err(_) => return Err(format!("{} timed out", command)),
+    };
+    let exit_code = docker
+        .inspect_exec(&exec.id)
+        .await
+        .ok()
+        .and_then(|i| i.exit_code)
+        .unwrap_or(-1);
+    match exit_code {
+        0 => Ok(()),
+        c => Err(format!(
+            "{} exited with code {}: {}",
+            command,
+            c,
+            output.trim()
+        )),
+    }
+}
+
+// Run due probes, returning containers that reached their failure threshold
+// Container addresses are only probed where the Docker host is this machine
+pub async fn run_probes(
+    docker: &Docker,
+    history: &mut ProbeHistoryList,
+    interval: u64,
+    remote: bool,
+) -> Vec<(ContainerSummary, inspect::Result)> {
+    let containers = match docker
+        .list_containers(None::<ListContainersOptions<String>>)
+        .await
+    {
+        Ok(list) => list,
+        Err(_) => return vec![],
+    };
+    // Forget containers that are gone or no longer running
+    history.state.retain(|id, _| {
+        containers
+            .iter()
+            .any(|c| c.id.as_ref().is_some_and(|i| i == id))
+    });
+    history.skipped.retain(|id| {
+        containers
+            .iter()
+            .any(|c| c.id.as_ref().is_some_and(|i| i == id))
+    });
+    let mut probes = vec![];
+    let mut thresholds = HashMap::new();
+    for container in containers {
+        let labels = container.labels.clone().unwrap_or_default();
+        let Some((kind, target)) = ["http", "tcp", "exec"].iter().find_map(|k| {
+            labels
+                .get(&format!("autoheal.probe.{}", k))
+                .map(|t| (k.to_string(), t.clone()))
+        }) else {
+            continue;
+        };
+        let id: String = container.id.clone().unwrap_or_default();
+        let probe_interval = labels
+            .get("autoheal.probe.interval")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(interval);
+        let timeout = labels
+            .get("autoheal.probe.timeout")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        let threshold: i64 = labels
+            .get("autoheal.probe.threshold")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+        // Skip probes that are not yet due
+        if history
+            .state
+            .get(&id)
+            .is_some_and(|s| s.last.elapsed() < Duration::from_secs(probe_interval))
+        {
+            continue;
+        }
+        let address = get_address(&container, labels.get("autoheal.probe.network"));
+        if needs_address(&kind, &target) && (remote || address.is_none()) {
+            if history.skipped.insert(id.clone()) {
+                let name = container
+                    .names
+                    .as_ref()
+                    .and_then(|n| n.first())
+                    .map(|n| n.trim_matches('/').to_string())
+                    .unwrap_or_default();
+                let reason = match remote {
+                    true => "the Docker host is remote; use host:port",
+                    false => "the container has no network address",
+                };
+                let msg0 = format!(
+                    "[{} ({})] Probe {} is skipped as {}",
+                    name,
+                    id.chars().take(12).collect::<String>(),
+                    target,
+                    reason
+                );
+                log_message(&msg0, WARNING).await;
+            }
+            continue;
+        }
+        history.skipped.remove(&id);
+        thresholds.insert(id.clone(), threshold);
+        probes.push(ProbeList {
+            address,
+            container,
+            id,
+            kind,
+            target,
+            timeout: Duration::from_secs(timeout),
+        });
+    }
+
+    // Probe concurrently so slow probes do not delay the cycle
+    let results = futures::future::join_all(probes.into_iter().map(|p| async move {
+        let result = match p.kind.as_str() {
+            "http" => probe_http(&p.target, p.address.as_deref().unwrap_or(""), p.timeout).await,
+            "tcp" => probe_tcp(&p.target, p.address.as_deref().unwrap_or(""), p.timeout).await,
+            _ => probe_exec(docker, &p.id, &p.target, p.timeout).await,
+        };
+        (p, result)
+    }))
+    .await;
+
+    let mut failed = vec![];
+    for (probe, result) in results {
+        let state = history
+            .state
+            .entry(probe.id.clone())
+            .or_insert(ProbeStateList {
+                last: Instant::now(),
+                failures: 0,
+            });
+        state.last = Instant::now();
+        match result {
+            Ok(()) => state.failures = 0,
+            Err(reason) => {
+                state.failures += 1;
+                if state.failures >= thresholds[&probe.id] {
+                    let failing_streak = state.failures;
+                    // Require the threshold again before acting on the restarted container
+                    state.failures = 0;
+                    failed.push((
+                        probe.container,
+                        inspect::Result::triggered(reason, failing_streak),
+                    ));
+                }
+            }
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::models::{ContainerSummaryNetworkSettings, EndpointSettings};
+
+    #[test]
+    fn needs_address_only_without_host() {
+        for (kind, target, expected) in [
+            ("http", "http://:8080/health", true),
+            ("http", "http:///health", true),
+            ("http", "http://app:8080/health", false),
+            ("tcp", "5432", true),
+            ("tcp", "db:5432", false),
+            ("exec", "pg_isready", false),
+        ] {
+            assert_eq!(needs_address(kind, target), expected, "{}", target);
+        }
+    }
+
+    #[test]
+    fn get_address_without_network_is_none() {
+        let endpoint = |ip: &str| EndpointSettings {
+            ip_address: Some(ip.to_string()),
+            ..Default::default()
+        };
+        let container = ContainerSummary {
+            network_settings: Some(ContainerSummaryNetworkSettings {
+                networks: Some(HashMap::from([
+                    ("host".to_string(), endpoint("")),
+                    ("web".to_string(), endpoint("172.18.0.2")),
+                ])),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_address(&container, None),
+            Some("172.18.0.2".to_string())
+        );
+        assert_eq!(get_address(&container, Some(&"host".to_string())), None);
+        assert_eq!(get_address(&ContainerSummary::default(), None), None);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{log_message, ALLOWED_ACTIONS, WARNING};
+use regex::Regex;
+use std::collections::HashMap;
+
+// Match a healthcheck output against a pattern, reporting invalid patterns
+async fn is_match(pattern: &str, output: &str, name: &str, id: &str) -> bool {
+    match Regex::new(pattern) {
+        Ok(r) => r.is_match(output),
+        Err(e) => {
+            let msg0 = format!("[{} ({})] Invalid pattern ({}): {}", name, id, pattern, e);
+            log_message(&msg0, WARNING).await;
+            false
+        }
+    }
+}
+
+// Choose the action for a failure from its healthcheck output and exit code
+// Precedence: ignore pattern, exit code rule, output rule, then restart
+pub async fn get_action(
+    labels: &HashMap<String, String>,
+    ignore_output: &str,
+    output: &str,
+    exit_code: i64,
+    name: &str,
+    id: &str,
+) -> (String, String) {
+    let (action, reason) = match_action(labels, ignore_output, output, exit_code, name, id).await;
+    match ALLOWED_ACTIONS.contains(&action.as_str()) {
+        true => (action, reason),
+        false => {
+            let msg0 = format!(
+                "[{} ({})] Unexpected action ({}): Expected one of {}; using default (restart)",
+                name,
+                id,
+                action,
+                ALLOWED_ACTIONS.join(",")
+            );
+            log_message(&msg0, WARNING).await;
+            ("restart".to_string(), reason)
+        }
+    }
+}
+
+async fn match_action(
+    labels: &HashMap<String, String>,
+    ignore_output: &str,
+    output: &str,
+    exit_code: i64,
+    name: &str,
+    id: &str,
+) -> (String, String) {
+    let ignore = match labels.get("autoheal.ignore.output") {
+        Some(p) => p.as_str(),
+        None => ignore_output,
+    };
+    if !ignore.is_empty() && is_match(ignore, output, name, id).await {
+        return ("ignore".to_string(), format!("output matches {}", ignore));
+    }
+    if let Some(action) = labels.get(&format!("autoheal.action.on.exitcode.{}", exit_code)) {
+        return (action.clone(), format!("exit code {}", exit_code));
+    }
+    // Evaluate output rules in a stable order
+    let mut rules: Vec<(&String, &String)> = labels
+        .iter()
+        .filter(|(k, _)| k.starts_with("autoheal.action.on.output."))
+        .collect();
+    rules.sort();
+    for (key, pattern) in rules {
+        if is_match(pattern, output, name, id).await {
+            let action = key.trim_start_matches("autoheal.action.on.output.");
+            return (action.to_string(), format!("output matches {}", pattern));
+        }
+    }
+    ("restart".to_string(), "".to_string())
+}
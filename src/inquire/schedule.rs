@@ -0,0 +1,400 @@
+use crate::{log_message, WARNING};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike,
+    Utc,
+};
+use chrono_tz::Tz;
+
+// Longest window allowed, bounding how far back window starts are searched
+const MAX_DURATION: i64 = 7 * 24 * 60 * 60;
+
+// Time in which a window is expressed
+enum Zone {
+    Local,
+    Offset(FixedOffset),
+    Named(Tz),
+}
+
+// Window opening whenever its cron expression matches and lasting for its duration
+struct WindowList {
+    zone: Zone,
+    minute: Vec<u32>,
+    hour: Vec<u32>,
+    day: Vec<u32>,
+    month: Vec<u32>,
+    weekday: Vec<u32>,
+    day_any: bool,
+    weekday_any: bool,
+    duration: i64,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// Parse a value of a cron field, accepting names of months and weekdays
+fn parse_value(value: &str, names: &[&str], offset: u32) -> Result<u32, String> {
+    match names.iter().position(|n| n.eq_ignore_ascii_case(value)) {
+        Some(p) => Ok(p as u32 + offset),
+        None => value
+            .parse()
+            .map_err(|_| format!("unexpected value {}", value)),
+    }
+}
+
+// Parse a cron field of values, ranges and steps, e.g. 1,15 or 9-17 or */10
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (
+                r,
+                s.parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or(format!("unexpected step {}", s))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (parse_value(a, names, min)?, parse_value(b, names, min)?),
+                None => {
+                    let v = parse_value(r, names, min)?;
+                    // A single value with a step runs to the end of the range, as in cron
+                    match part.contains('/') {
+                        true => (v, max),
+                        false => (v, v),
+                    }
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{} is outside {}-{}", part, min, max));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    // Sorted so the latest start of a day can be found from the end
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+// Parse a duration such as 90m, 2h or 1d, in seconds where no unit is given
+fn parse_duration(duration: &str) -> Result<i64, String> {
+    let (value, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => duration.split_at(i),
+        None => (duration, "s"),
+    };
+    let value: i64 = value
+        .parse()
+        .map_err(|_| format!("unexpected duration {}", duration))?;
+    let seconds = match unit.to_lowercase().as_str() {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 60 * 60,
+        "d" => value * 24 * 60 * 60,
+        _ => return Err(format!("unexpected duration {}", duration)),
+    };
+    match seconds > 0 && seconds <= MAX_DURATION {
+        true => Ok(seconds),
+        false => Err(format!("duration {} is outside 1s-7d", duration)),
+    }
+}
+
+// Parse a window such as "Europe/Berlin 0 2 * * * 2h": optional UTC, offset or time zone name,
+// cron expression and duration; time zone names use the database compiled into the binary
+fn parse_window(window: &str) -> Result<WindowList, String> {
+    let mut fields: Vec<&str> = window.split_whitespace().collect();
+    let zone = match fields.first() {
+        Some(f) if f.eq_ignore_ascii_case("utc") => {
+            fields.remove(0);
+            Zone::Named(Tz::UTC)
+        }
+        Some(f) if f.starts_with('+') || f.starts_with('-') => {
+            let offset = f
+                .parse::<FixedOffset>()
+                .map_err(|_| format!("unexpected offset {}", f))?;
+            fields.remove(0);
+            Zone::Offset(offset)
+        }
+        Some(f) if fields.len() == 7 => {
+            let tz = f
+                .parse::<Tz>()
+                .map_err(|_| format!("unexpected time zone {}", f))?;
+            fields.remove(0);
+            Zone::Named(tz)
+        }
+        _ => Zone::Local,
+    };
+    let [minute, hour, day, month, weekday, duration] = fields[..] else {
+        return Err("expected a cron expression of 5 fields and a duration".to_string());
+    };
+    let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAYS)?;
+    // Both 0 and 7 are Sunday
+    for w in weekdays.iter_mut() {
+        *w %= 7;
+    }
+    weekdays.sort_unstable();
+    weekdays.dedup();
+    Ok(WindowList {
+        zone,
+        minute: parse_field(minute, 0, 59, &[])?,
+        hour: parse_field(hour, 0, 23, &[])?,
+        day: parse_field(day, 1, 31, &[])?,
+        month: parse_field(month, 1, 12, &MONTHS)?,
+        weekday: weekdays,
+        day_any: day == "*",
+        weekday_any: weekday == "*",
+        duration: parse_duration(duration)?,
+    })
+}
+
+// Parse windows separated by ;
+fn parse_schedule(schedule: &str) -> Result<Vec<WindowList>, String> {
+    schedule
+        .split(';')
+        .filter(|w| !w.trim().is_empty())
+        .map(parse_window)
+        .collect()
+}
+
+// Whether a window opens on the given date; day of month and weekday either match when both are set
+fn is_date(window: &WindowList, date: &NaiveDate) -> bool {
+    let day = window.day.contains(&date.day());
+    let weekday = window
+        .weekday
+        .contains(&date.weekday().num_days_from_sunday());
+    let matched = match (window.day_any, window.weekday_any) {
+        (true, true) => true,
+        (true, false) => weekday,
+        (false, true) => day,
+        (false, false) => day || weekday,
+    };
+    matched && window.month.contains(&date.month())
+}
+
+// Most recent time at or before now that a window opened, looking back no further than its duration
+fn previous_start(window: &WindowList, now: &NaiveDateTime) -> Option<NaiveDateTime> {
+    let earliest = (*now - Duration::seconds(window.duration)).date();
+    let mut date = now.date();
+    while date >= earliest {
+        if is_date(window, &date) {
+            // Only today is bounded by the current time
+            let (hour, minute) = match date == now.date() {
+                true => (now.hour(), now.minute()),
+                false => (23, 59),
+            };
+            for h in window.hour.iter().rev().filter(|h| **h <= hour) {
+                if let Some(m) = window
+                    .minute
+                    .iter()
+                    .rev()
+                    .find(|m| *h < hour || **m <= minute)
+                {
+                    return date.and_hms_opt(*h, *m, 0);
+                }
+            }
+        }
+        date = date.pred_opt()?;
+    }
+    None
+}
+
+// Convert a time of a window's zone to UTC, taking the earlier of times repeated by a clock change
+fn to_utc(zone: &Zone, time: &NaiveDateTime) -> Option<DateTime<Utc>> {
+    match zone {
+        Zone::Local => Local
+            .from_local_datetime(time)
+            .earliest()
+            .map(|t| t.to_utc()),
+        Zone::Offset(o) => o.from_local_datetime(time).earliest().map(|t| t.to_utc()),
+        Zone::Named(tz) => tz.from_local_datetime(time).earliest().map(|t| t.to_utc()),
+    }
+}
+
+// Whether a window opened within its duration before now, in its own zone or local time
+fn is_open(window: &WindowList, now: DateTime<Utc>) -> bool {
+    let local = match &window.zone {
+        Zone::Local => now.with_timezone(&Local).naive_local(),
+        Zone::Offset(o) => now.with_timezone(o).naive_local(),
+        Zone::Named(tz) => now.with_timezone(tz).naive_local(),
+    };
+    let Some(start) = previous_start(window, &local) else {
+        return false;
+    };
+    // Measured in UTC so a window spanning a daylight saving change keeps its duration
+    let elapsed = match to_utc(&window.zone, &start) {
+        Some(s) => now - s,
+        None => local - start,
+    };
+    elapsed.num_seconds() < window.duration
+}
+
+// Validate a schedule, returning the reason it is invalid
+pub fn check_schedule(schedule: &str) -> Result<(), String> {
+    parse_schedule(schedule).map(|_| ())
+}
+
+// Whether any window of a schedule is open now, reporting invalid schedules
+pub async fn is_scheduled(schedule: &str, name: &str, id: &str) -> bool {
+    if schedule.is_empty() {
+        return false;
+    }
+    match parse_schedule(schedule) {
+        Ok(windows) => windows.iter().any(|w| is_open(w, Utc::now())),
+        Err(e) => {
+            let msg0 = format!("[{} ({})] Invalid schedule ({}): {}", name, id, schedule, e);
+            log_message(&msg0, WARNING).await;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_field_expands_values() {
+        let none: &[&str] = &[];
+        let cases = [
+            ("*", 0, 6, none, vec![0, 1, 2, 3, 4, 5, 6]),
+            ("*/15", 0, 59, none, vec![0, 15, 30, 45]),
+            ("9-17/4", 0, 23, none, vec![9, 13, 17]),
+            ("5/20", 0, 59, none, vec![5, 25, 45]),
+            ("15,1,15", 1, 31, none, vec![1, 15]),
+            ("1-3,2-4", 1, 31, none, vec![1, 2, 3, 4]),
+            ("jan-mar", 1, 12, &MONTHS[..], vec![1, 2, 3]),
+            ("Dec,jun", 1, 12, &MONTHS[..], vec![6, 12]),
+            ("mon-fri", 0, 7, &WEEKDAYS[..], vec![1, 2, 3, 4, 5]),
+        ];
+        for (field, min, max, names, expected) in cases {
+            assert_eq!(
+                parse_field(field, min, max, names),
+                Ok(expected),
+                "{}",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn parse_field_rejects_invalid() {
+        for (field, min, max) in [
+            ("60", 0, 59),
+            ("0", 1, 31),
+            ("5-1", 0, 59),
+            ("*/0", 0, 59),
+            ("*/x", 0, 59),
+            ("foo", 0, 59),
+            ("", 0, 59),
+        ] {
+            assert!(parse_field(field, min, max, &[]).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn parse_window_treats_0_and_7_as_sunday() {
+        for (weekday, expected) in [
+            ("0", vec![0]),
+            ("7", vec![0]),
+            ("0,7", vec![0]),
+            ("5-7", vec![0, 5, 6]),
+            ("sun", vec![0]),
+        ] {
+            let window = parse_window(&format!("0 0 * * {} 1h", weekday)).unwrap();
+            assert_eq!(window.weekday, expected, "{}", weekday);
+        }
+    }
+
+    #[test]
+    fn parse_window_rejects_invalid() {
+        for window in [
+            "0 2 * * *",
+            "0 2 * * * 8d",
+            "0 2 * * * 0m",
+            "0 2 * * * 2w",
+            "+25:00 0 2 * * * 2h",
+            "Mars/Olympus 0 2 * * * 2h",
+        ] {
+            assert!(parse_window(window).is_err(), "{}", window);
+        }
+        assert!(parse_window("Mars/Olympus 0 2 * * * 2h")
+            .err()
+            .unwrap()
+            .contains("time zone"));
+        assert!(check_schedule(
+            "UTC 0 2 * * * 2h; +02:00 30 1 * * sat 90m; Europe/Berlin 0 3 * * * 1h"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn is_open_follows_schedule() {
+        // 2026-10-19 is a Monday
+        let cases = [
+            ("UTC 0 2 * * * 2h", (2026, 10, 19, 1, 59), false),
+            ("UTC 0 2 * * * 2h", (2026, 10, 19, 2, 0), true),
+            ("UTC 0 2 * * * 2h", (2026, 10, 19, 3, 59), true),
+            ("UTC 0 2 * * * 2h", (2026, 10, 19, 4, 0), false),
+            // Across midnight and across days
+            ("UTC 0 22 * * * 4h", (2026, 10, 20, 1, 30), true),
+            ("UTC 0 20 * * fri 3d", (2026, 10, 19, 10, 0), true),
+            ("UTC 0 20 * * fri 3d", (2026, 10, 19, 20, 0), false),
+            // Steps, ranges and names
+            ("UTC */30 9-17 * * mon-fri 10m", (2026, 10, 19, 12, 5), true),
+            (
+                "UTC */30 9-17 * * mon-fri 10m",
+                (2026, 10, 19, 12, 15),
+                false,
+            ),
+            (
+                "UTC */30 9-17 * * mon-fri 10m",
+                (2026, 10, 19, 18, 5),
+                false,
+            ),
+            (
+                "UTC */30 9-17 * * mon-fri 10m",
+                (2026, 10, 24, 12, 5),
+                false,
+            ),
+            ("UTC 0 0 1 jan * 1d", (2026, 1, 1, 5, 0), true),
+            ("UTC 0 0 1 jan * 1d", (2026, 2, 1, 5, 0), false),
+            // Sunday as 0 or 7
+            ("UTC 0 0 * * 7 1d", (2026, 10, 18, 12, 0), true),
+            ("UTC 0 0 * * 0 1d", (2026, 10, 18, 12, 0), true),
+            ("UTC 0 0 * * 7 1d", (2026, 10, 19, 12, 0), false),
+            // Day of month or weekday when both are set, otherwise whichever is
+            ("UTC 0 0 13 * fri 1d", (2026, 10, 13, 12, 0), true),
+            ("UTC 0 0 13 * fri 1d", (2026, 10, 16, 12, 0), true),
+            ("UTC 0 0 13 * fri 1d", (2026, 10, 15, 12, 0), false),
+            ("UTC 0 0 13 * * 1d", (2026, 10, 16, 12, 0), false),
+            ("UTC 0 0 * * fri 1d", (2026, 10, 13, 12, 0), false),
+            // Offsets
+            ("+02:00 0 2 * * * 1h", (2026, 10, 19, 0, 30), true),
+            ("+02:00 0 2 * * * 1h", (2026, 10, 19, 2, 30), false),
+            ("-05:00 0 22 * * sun 3h", (2026, 10, 19, 4, 0), true),
+            // Time zones follow daylight saving time, which in Berlin ends on 2026-10-25
+            ("Europe/Berlin 0 2 * * * 1h", (2026, 10, 19, 0, 30), true),
+            ("Europe/Berlin 0 2 * * * 1h", (2026, 10, 19, 1, 30), false),
+            ("Europe/Berlin 0 2 * * * 1h", (2026, 11, 2, 0, 30), false),
+            ("Europe/Berlin 0 2 * * * 1h", (2026, 11, 2, 1, 30), true),
+            // Opening at 22:00 UTC, a 3h window is closed at 02:30 local time after the change
+            ("Europe/Berlin 0 0 25 oct * 3h", (2026, 10, 25, 0, 30), true),
+            (
+                "Europe/Berlin 0 0 25 oct * 3h",
+                (2026, 10, 25, 1, 30),
+                false,
+            ),
+        ];
+        for (schedule, (y, mo, d, h, mi), expected) in cases {
+            let now = Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap();
+            let window = parse_window(schedule).unwrap();
+            assert_eq!(is_open(&window, now), expected, "{} at {}", schedule, now);
+        }
+    }
+}
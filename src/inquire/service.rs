@@ -0,0 +1,35 @@
+use bollard::Docker;
+use std::collections::HashMap;
+
+// Swarm service a task container belongs to
+pub struct ServiceList {
+    pub id: String,
+    pub name: String,
+    pub labels: HashMap<String, String>,
+}
+
+// Resolve the Swarm service of a task container along with its service-level labels
+pub async fn get_service(docker: &Docker, labels: &HashMap<String, String>) -> Option<ServiceList> {
+    let id = labels.get("com.docker.swarm.service.id")?.clone();
+    let mut service = ServiceList {
+        name: labels
+            .get("com.docker.swarm.service.name")
+            .cloned()
+            .unwrap_or(id.clone()),
+        id,
+        labels: HashMap::new(),
+    };
+    // Service labels (e.g. deploy.labels) are not applied to task containers
+    if let Some(spec) = docker
+        .inspect_service(&service.id, None)
+        .await
+        .ok()
+        .and_then(|s| s.spec)
+    {
+        if let Some(name) = spec.name {
+            service.name = name;
+        }
+        service.labels = spec.labels.unwrap_or_default();
+    }
+    Some(service)
+}
@@ -0,0 +1,258 @@
+use super::{background::BackgroundTask, inspect};
+use crate::{log_message, DEBUG};
+use bollard::{
+    container::{ListContainersOptions, MemoryStatsStats, Stats, StatsOptions},
+    models::ContainerSummary,
+    Docker,
+};
+use futures::StreamExt;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// Default time in seconds a threshold must be exceeded before acting
+const DEFAULT_DURATION: u64 = 300;
+
+// Usage of a container carried between samples
+#[derive(Default)]
+struct StatsStateList {
+    cpu_total: u64,
+    cpu_system: u64,
+    memory_since: Option<Instant>,
+    cpu_since: Option<Instant>,
+}
+
+// Sampling state per container id, and containers that exceeded a threshold for long enough
+#[derive(Default)]
+pub struct StatsHistoryList {
+    sampler: Option<BackgroundTask>,
+    state: HashMap<String, StatsStateList>,
+    triggered: Vec<(ContainerSummary, inspect::Result)>,
+}
+
+// Thresholds declared by labels on a container
+struct LimitsList {
+    container: ContainerSummary,
+    id: String,
+    name: String,
+    memory_percent: Option<f64>,
+    memory_duration: u64,
+    cpu_percent: Option<f64>,
+    cpu_duration: u64,
+}
+
+// Memory in use as a percentage of the limit, discounting reclaimable page cache as docker stats does
+fn memory_percent(stats: &Stats) -> Option<f64> {
+    let usage = stats.memory_stats.usage?;
+    let limit = stats.memory_stats.limit.filter(|l| *l > 0)?;
+    let cache = match stats.memory_stats.stats {
+        Some(MemoryStatsStats::V1(s)) => s.total_inactive_file,
+        Some(MemoryStatsStats::V2(s)) => s.inactive_file,
+        None => 0,
+    };
+    Some(usage.saturating_sub(cache) as f64 / limit as f64 * 100.0)
+}
+
+// CPU in use since the previous sample as a percentage of one CPU, as docker stats does
+fn cpu_percent(stats: &Stats, state: &StatsStateList) -> Option<f64> {
+    let system = stats.cpu_stats.system_cpu_usage?;
+    if state.cpu_system == 0 || system <= state.cpu_system {
+        return None;
+    }
+    let cpu = stats
+        .cpu_stats
+        .cpu_usage
+        .total_usage
+        .saturating_sub(state.cpu_total);
+    let cpus = stats.cpu_stats.online_cpus.unwrap_or(1);
+    Some(cpu as f64 / (system - state.cpu_system) as f64 * cpus as f64 * 100.0)
+}
+
+// Track how long a threshold has been exceeded, returning the reason once sustained
+fn check_limit(
+    since: &mut Option<Instant>,
+    kind: &str,
+    value: Option<f64>,
+    limit: Option<f64>,
+    duration: u64,
+) -> Option<String> {
+    let (Some(value), Some(limit)) = (value, limit) else {
+        *since = None;
+        return None;
+    };
+    if value <= limit {
+        *since = None;
+        return None;
+    }
+    let elapsed = since.get_or_insert(Instant::now()).elapsed().as_secs();
+    match elapsed >= duration {
+        true => {
+            // Require the duration again before acting on the restarted container
+            *since = None;
+            Some(format!(
+                "{} usage {:.1}% above {}% for {}s",
+                kind, value, limit, elapsed
+            ))
+        }
+        false => None,
+    }
+}
+
+// Sample containers with resource thresholds, recording those that exceeded them for long enough
+async fn sample_stats(docker: &Docker, history: &Mutex<StatsHistoryList>) {
+    let containers = match docker
+        .list_containers(None::<ListContainersOptions<String>>)
+        .await
+    {
+        Ok(list) => list,
+        Err(_) => return,
+    };
+    let mut limits = vec![];
+    for container in containers {
+        let labels = container.labels.clone().unwrap_or_default();
+        let memory_percent = labels
+            .get("autoheal.memory.max_percent")
+            .and_then(|v| v.parse().ok());
+        let cpu_percent = labels
+            .get("autoheal.cpu.max_percent")
+            .and_then(|v| v.parse().ok());
+        if memory_percent.is_none() && cpu_percent.is_none() {
+            continue;
+        }
+        limits.push(LimitsList {
+            id: container.id.clone().unwrap_or_default(),
+            name: container
+                .names
+                .as_ref()
+                .and_then(|n| n.first())
+                .map(|n| n.trim_matches('/').to_string())
+                .unwrap_or_default(),
+            memory_percent,
+            memory_duration: labels
+                .get("autoheal.memory.duration")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_DURATION),
+            cpu_percent,
+            cpu_duration: labels
+                .get("autoheal.cpu.duration")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_DURATION),
+            container,
+        });
+    }
+    // Forget containers that are gone, no longer running or no longer limited
+    history
+        .lock()
+        .unwrap()
+        .state
+        .retain(|id, _| limits.iter().any(|l| &l.id == id));
+
+    // Sample concurrently so a slow engine does not delay other containers
+    let options = StatsOptions {
+        stream: false,
+        one_shot: true,
+    };
+    let samples = futures::future::join_all(limits.into_iter().map(|l| async move {
+        let stats = docker.stats(&l.id, Some(options)).next().await;
+        (l, stats)
+    }))
+    .await;
+
+    for (limit, stats) in samples {
+        let Some(Ok(stats)) = stats else {
+            continue;
+        };
+        let mut waiting = vec![];
+        let reason = {
+            let mut history = history.lock().unwrap();
+            let state = history.state.entry(limit.id.clone()).or_default();
+            let memory = memory_percent(&stats);
+            let cpu = cpu_percent(&stats, state);
+            state.cpu_total = stats.cpu_stats.cpu_usage.total_usage;
+            state.cpu_system = stats.cpu_stats.system_cpu_usage.unwrap_or(0);
+            let reasons: Vec<String> = [
+                check_limit(
+                    &mut state.memory_since,
+                    "Memory",
+                    memory,
+                    limit.memory_percent,
+                    limit.memory_duration,
+                ),
+                check_limit(
+                    &mut state.cpu_since,
+                    "CPU",
+                    cpu,
+                    limit.cpu_percent,
+                    limit.cpu_duration,
+                ),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            if let (Some(s), Some(v), Some(l)) = (state.memory_since, memory, limit.memory_percent)
+            {
+                waiting.push(format!(
+                    "memory usage {:.1}% above {}% for {}s",
+                    v,
+                    l,
+                    s.elapsed().as_secs()
+                ));
+            }
+            if let (Some(s), Some(v), Some(l)) = (state.cpu_since, cpu, limit.cpu_percent) {
+                waiting.push(format!(
+                    "CPU usage {:.1}% above {}% for {}s",
+                    v,
+                    l,
+                    s.elapsed().as_secs()
+                ));
+            }
+            reasons.join("; ")
+        };
+        let id: String = limit.id.chars().take(12).collect();
+        if !waiting.is_empty() {
+            let msg0 = format!(
+                "[{} ({})] Container {}; waiting before acting",
+                limit.name,
+                id,
+                waiting.join(", ")
+            );
+            log_message(&msg0, DEBUG).await;
+        }
+        if reason.is_empty() {
+            continue;
+        }
+        history
+            .lock()
+            .unwrap()
+            .triggered
+            .push((limit.container, inspect::Result::triggered(reason, 1)));
+    }
+}
+
+// Sample container stats in the background so slow responses do not block the main loop,
+// until the returned history is dropped
+pub fn start_stats(docker: Docker, interval: u64) -> Arc<Mutex<StatsHistoryList>> {
+    let history = Arc::new(Mutex::new(StatsHistoryList::default()));
+    let shared = Arc::downgrade(&history);
+    let sampler = BackgroundTask::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            interval.tick().await;
+            let Some(history) = shared.upgrade() else {
+                break;
+            };
+            sample_stats(&docker, &history).await;
+        }
+    });
+    history.lock().unwrap().sampler = Some(sampler);
+    history
+}
+
+// Containers that exceeded a resource threshold since the last call
+pub fn take_triggered(
+    history: &Mutex<StatsHistoryList>,
+) -> Vec<(ContainerSummary, inspect::Result)> {
+    std::mem::take(&mut history.lock().unwrap().triggered)
+}
@@ -1,28 +1,59 @@
-use libc::{access, W_OK};
-use std::{ffi::CString, time::Duration};
+use std::time::Duration;
 // Docker-Autoheal modules
 mod execute {
     pub mod action;
     pub mod connect;
+    pub mod dependants;
+    pub mod exited;
     pub mod looper;
     pub mod postaction;
+    pub mod swarm;
+    pub mod tunnel;
 }
 mod inquire {
+    pub mod background;
+    pub mod compose;
+    pub mod context;
+    pub mod depends;
+    pub mod endpoints;
     pub mod environment;
+    pub mod events;
+    pub mod identity;
     pub mod inspect;
     pub mod list;
+    pub mod logs;
     pub mod options;
+    pub mod patterns;
+    pub mod pod;
+    pub mod probe;
+    pub mod rules;
+    pub mod schedule;
+    pub mod service;
+    pub mod stats;
 }
 mod report {
+    pub mod diagnostics;
+    pub mod history;
     pub mod logging;
     pub mod record;
     pub mod webhook;
 }
 
 // Docker-Autoheal functions
-use execute::{connect::connect_docker, looper::start_loop};
-use inquire::{environment::get_var, options::get_opts};
-use report::logging::log_message;
+use execute::{
+    connect::{connect_docker, is_remote, ConnectionVariablesList},
+    looper::{start_endpoints, start_loop},
+};
+use inquire::{
+    endpoints::get_endpoints,
+    environment::{get_log_path, get_var},
+    options::{get_history_opts, get_opts},
+};
+use report::{
+    history::show_history,
+    logging::{log_message, set_log_debug},
+    record::{probe_record, RetentionList},
+};
 
 // Current year
 const YEAR: i32 = 2026;
@@ -31,14 +62,41 @@ const YEAR: i32 = 2026;
 const INFO: i8 = 0;
 const WARNING: i8 = 1;
 const ERROR: i8 = 2;
+const DEBUG: i8 = 3;
 
 // Allowed connection types
-const ALLOWED_CONNECTION_TYPES: [&str; 4] = ["local", "socket", "http", "ssl"];
+const ALLOWED_CONNECTION_TYPES: [&str; 5] = ["local", "socket", "http", "ssl", "ssh"];
+
+// Allowed identity types
+const ALLOWED_IDENTITY_TYPES: [&str; 3] = ["name", "id", "compose"];
+
+// Allowed container runtimes and pod restart scopes
+const ALLOWED_RUNTIMES: [&str; 3] = ["auto", "docker", "podman"];
+const ALLOWED_POD_RESTARTS: [&str; 2] = ["container", "pod"];
+
+// Allowed actions on a failure, as chosen by healthcheck output rules
+const ALLOWED_ACTIONS: [&str; 4] = ["restart", "stop", "notify", "ignore"];
+
+// Allowed restart scopes of compose project members
+const ALLOWED_RESTART_SCOPES: [&str; 3] = ["container", "dependents", "project"];
+
+// Allowed remediation of Swarm task containers
+const ALLOWED_SWARM_ACTIONS: [&str; 3] = ["restart", "update", "remove"];
+
+// Allowed history subcommand arguments
+const ALLOWED_HISTORY_FORMATS: [&str; 2] = ["table", "json"];
+const ALLOWED_HISTORY_OUTCOMES: [&str; 4] = ["success", "failed", "disabled", "other"];
 
 // External logging
 const LOG_PATH: &str = "/opt/docker-autoheal/";
 const LOG_FILE: &str = "log.json";
+const SUMMARY_FILE: &str = "summary.json";
+const QUARANTINE_FILE: &str = "log.json.quarantine";
+const LOCK_FILE: &str = ".log.lock";
+const INCIDENT_PATH: &str = "incidents/";
+const DIAGNOSTICS_PATH: &str = "diagnostics/";
 
+#[derive(Clone)]
 struct LoopVariablesList {
     stop_timeout: isize,
     interval: u64,
@@ -48,18 +106,53 @@ struct LoopVariablesList {
     post_action: String,
     log_all: bool,
     monitor_all: bool,
+    monitor_exited: bool,
+    exited_budget: u64,
+    failing_threshold: i64,
+    unhealthy_duration: u64,
+    ignore_output: String,
+    log_tail: u64,
+    log_tail_bytes: u64,
+    log_tail_notify: bool,
+    diagnostics: bool,
+    diagnostics_export: bool,
+    log_pattern: String,
+    log_pattern_threshold: usize,
+    log_pattern_window: u64,
+    skip_restart_no: bool,
+    deploy_window: u64,
+    maintenance: String,
+    quiet_hours: String,
     pub log_ready: bool,
+    log_path: String,
+    log_quarantine: bool,
+    identity_type: String,
+    runtime: String,
+    pod_restart: String,
+    swarm_action: String,
+    restart_scope: String,
+    retention: RetentionList,
+    remote: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Collect binary arguments
-    let args = std::env::args().collect();
+    let args: Vec<String> = std::env::args().collect();
+
+    // Query persistent log data without starting the monitor
+    if args.get(1).is_some_and(|a| a == "history") {
+        let opt = get_history_opts(args);
+        let log_path = get_log_path(opt.log_path.clone());
+        return show_history(opt, log_path).await;
+    }
+
     let opt = get_opts(args);
 
     // Get Autoheal core variables
     // Determine if we have valid arguments, need to check env, or use defaults
     let var = get_var(opt).await;
+    set_log_debug(var.log_debug);
 
     // Delay start of monitoring, if specified
     if var.start_delay > 0 {
@@ -70,32 +163,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         log_message(&msg1, INFO).await;
     }
 
-    // Connect to Docker per type
-    let docker = connect_docker(
-        var.connection_type,
-        var.tcp_address,
-        var.tcp_timeout,
-        var.key_path,
-        var.cert_path,
-        var.ca_path,
-    )
-    .await;
-
-    // Determine if log path is present and writeable
+    // Determine if log path is present and writeable, creating it if permitted
     let mut log_ready = false;
     if var.log_persist {
-        let c_path = CString::new(LOG_PATH).unwrap();
-        log_ready = unsafe { access(c_path.as_ptr(), W_OK) == 0 };
-        if !log_ready {
-            let msg0 = format!(
-                "Readonly filesystem ({}); external logging is disabled",
-                LOG_PATH
-            );
-            log_message(&msg0, INFO).await;
-        }
+        log_ready = match probe_record(&var.log_path).await {
+            Ok(()) => true,
+            Err(e) => {
+                let reason = match e.kind() {
+                    std::io::ErrorKind::ReadOnlyFilesystem => "Readonly filesystem",
+                    std::io::ErrorKind::PermissionDenied => "Permission denied",
+                    std::io::ErrorKind::NotFound => "Path not found",
+                    _ => "Path not writeable",
+                };
+                let msg0 = format!(
+                    "{} ({}); external logging is disabled: {}",
+                    reason, var.log_path, e
+                );
+                log_message(&msg0, WARNING).await;
+                false
+            }
+        };
     }
 
-    let loop_variables = {
+    // Gather Docker hosts to monitor, if specified
+    let endpoints = match var.hosts_file.is_empty() {
+        true => vec![],
+        false => get_endpoints(&var).await,
+    };
+
+    let mut loop_variables = {
         LoopVariablesList {
             stop_timeout: var.stop_timeout,
             interval: var.interval,
@@ -105,10 +201,63 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             post_action: var.post_action,
             log_all: var.log_all,
             monitor_all: var.monitor_all,
+            monitor_exited: var.monitor_exited,
+            exited_budget: var.exited_budget,
+            failing_threshold: var.failing_threshold,
+            unhealthy_duration: var.unhealthy_duration,
+            ignore_output: var.ignore_output,
+            log_tail: var.log_tail,
+            log_tail_bytes: var.log_tail_bytes,
+            log_tail_notify: var.log_tail_notify,
+            diagnostics: var.diagnostics,
+            diagnostics_export: var.diagnostics_export,
+            log_pattern: var.log_pattern,
+            log_pattern_threshold: var.log_pattern_threshold,
+            log_pattern_window: var.log_pattern_window,
+            skip_restart_no: var.skip_restart_no,
+            deploy_window: var.deploy_window,
+            maintenance: var.maintenance,
+            quiet_hours: var.quiet_hours,
             log_ready,
+            log_path: var.log_path,
+            log_quarantine: var.log_quarantine,
+            identity_type: var.identity_type,
+            runtime: var.runtime,
+            pod_restart: var.pod_restart,
+            swarm_action: var.swarm_action,
+            restart_scope: var.restart_scope,
+            retention: RetentionList {
+                max_age: var.log_max_age,
+                max_size: var.log_max_size,
+                max_files: var.log_max_files,
+                max_artifacts: var.log_max_artifacts,
+                compress: var.log_compress,
+            },
+            remote: false,
         }
     };
 
+    // Begin work on each Docker host concurrently
+    if !var.hosts_file.is_empty() {
+        return start_endpoints(loop_variables, endpoints).await;
+    }
+
+    // Connect to Docker per type
+    let connection = ConnectionVariablesList {
+        connection_type: var.connection_type,
+        tcp_address: var.tcp_address,
+        tcp_timeout: var.tcp_timeout,
+        key_path: var.key_path,
+        cert_path: var.cert_path,
+        ca_path: var.ca_path,
+        socket_path: var.socket_path,
+        ssh_host: var.ssh_host,
+        ssh_key: var.ssh_key,
+        ssh_known_hosts: var.ssh_known_hosts,
+    };
+    loop_variables.remote = is_remote(&connection);
+    let docker = connect_docker(connection).await;
+
     // Begin work
     start_loop(loop_variables, docker).await
 }
@@ -0,0 +1,73 @@
+use crate::DIAGNOSTICS_PATH;
+use bollard::{
+    container::{InspectContainerOptions, StatsOptions, TopOptions},
+    Docker,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::{fs::File, io::AsyncWriteExt, io::Error};
+
+async fn write_json<T: Serialize>(path: &str, data: &T) -> Result<(), Error> {
+    let content = serde_json::to_vec_pretty(data)?;
+    tokio::fs::write(path, content).await
+}
+
+// Capture inspect, stats, top and filesystem diff, optionally with an export of the filesystem
+// Artifacts are collected on a best effort basis, returning those that could not be captured
+pub async fn write_diagnostics(
+    docker: &Docker,
+    log_path: &str,
+    bundle: &str,
+    id: &str,
+    export: bool,
+) -> Result<Vec<String>, Error> {
+    let bundle_path = format!("{}{}{}/", log_path, DIAGNOSTICS_PATH, bundle);
+    tokio::fs::create_dir_all(&bundle_path).await?;
+    let mut failures = vec![];
+
+    match docker
+        .inspect_container(id, None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(i) => write_json(&(bundle_path.clone() + "inspect.json"), &i).await?,
+        Err(e) => failures.push(format!("inspect ({})", e)),
+    }
+
+    let options = Some(StatsOptions {
+        stream: false,
+        one_shot: true,
+    });
+    match docker.stats(id, options).next().await {
+        Some(Ok(s)) => write_json(&(bundle_path.clone() + "stats.json"), &s).await?,
+        Some(Err(e)) => failures.push(format!("stats ({})", e)),
+        None => failures.push("stats (no data)".to_string()),
+    }
+
+    let options = Some(TopOptions { ps_args: "aux" });
+    match docker.top_processes(id, options).await {
+        Ok(t) => write_json(&(bundle_path.clone() + "top.json"), &t).await?,
+        Err(e) => failures.push(format!("top ({})", e)),
+    }
+
+    match docker.container_changes(id).await {
+        Ok(d) => write_json(&(bundle_path.clone() + "diff.json"), &d.unwrap_or_default()).await?,
+        Err(e) => failures.push(format!("diff ({})", e)),
+    }
+
+    // Stream the export to disk as it may be large
+    if export {
+        let mut file = File::create(bundle_path.clone() + "export.tar").await?;
+        let mut stream = docker.export_container(id);
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(c) => file.write_all(&c).await?,
+                Err(e) => {
+                    failures.push(format!("export ({})", e));
+                    break;
+                }
+            }
+        }
+        file.flush().await?;
+    }
+    Ok(failures)
+}
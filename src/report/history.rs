@@ -0,0 +1,327 @@
+use super::record::{read_history, read_summary, record_key, JsonRecord};
+use crate::{inquire::options::HistoryOptionsList, LOG_FILE};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize)]
+struct ContainerSummary {
+    name: String,
+    incidents: usize,
+    success: usize,
+    failed: usize,
+    success_rate: Option<f64>,
+    mtbf_seconds: Option<i64>,
+    compacted: u64,
+}
+
+// Classify the action taken for a record
+// Records written before the outcome was recorded are classified by their message
+fn outcome(record: &JsonRecord) -> &str {
+    if !record.outcome.is_empty() {
+        record.outcome.as_str()
+    } else if record.action.contains("restart was successful") {
+        "success"
+    } else if record.action.contains("restart failed") {
+        "failed"
+    } else if record.action.contains("restart is disabled")
+        || record.action == "Restart enabled: false"
+    {
+        "disabled"
+    } else {
+        "other"
+    }
+}
+
+fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S%z").ok()
+}
+
+// Accept an absolute local date/time or an age relative to now (e.g. 7d, 12h, 30m)
+fn parse_bound(bound: &str) -> Option<DateTime<FixedOffset>> {
+    let now = Local::now().fixed_offset();
+    let (value, unit) = bound.split_at(bound.len().saturating_sub(1));
+    if let Ok(n) = value.parse::<i64>() {
+        match unit {
+            "d" => return Some(now - Duration::days(n)),
+            "h" => return Some(now - Duration::hours(n)),
+            "m" => return Some(now - Duration::minutes(n)),
+            _ => (),
+        }
+    }
+    let naive = NaiveDateTime::parse_from_str(bound, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(bound, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .single()
+        .map(|d| d.fixed_offset())
+}
+
+fn format_duration(seconds: i64) -> String {
+    let (d, h, m, s) = (
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    );
+    match d > 0 {
+        true => format!("{}d {}h {}m", d, h, m),
+        false => format!("{}h {}m {}s", h, m, s),
+    }
+}
+
+// Query persistent log data without starting the monitor
+pub async fn show_history(
+    opt: HistoryOptionsList,
+    log_path: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let set = match read_history(&log_path).await {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("Unable to read from log ({}{}): {}", log_path, LOG_FILE, e);
+            std::process::exit(1);
+        }
+    };
+    if !set.corrupt.is_empty() {
+        eprintln!("Skipped {} corrupt line(s)", set.corrupt.len());
+    }
+    let summary = read_summary(&log_path).await.unwrap_or_default();
+
+    // Validate time range
+    let mut bounds = vec![];
+    for bound in [&opt.since, &opt.until] {
+        bounds.push(match bound {
+            Some(b) => match parse_bound(b) {
+                Some(d) => Some(d),
+                None => {
+                    eprintln!("Unexpected date/time: {}", b);
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        });
+    }
+    let (since, until) = (bounds[0], bounds[1]);
+
+    // Apply filters
+    let mut records: Vec<(DateTime<FixedOffset>, JsonRecord)> = set
+        .records
+        .into_iter()
+        .filter_map(|r| parse_date(&r.date).map(|d| (d, r)))
+        .filter(|(_, r)| match &opt.container {
+            // Accept either a short or full length id
+            Some(c) => {
+                &r.name == c
+                    || &r.identity == c
+                    || (!c.is_empty() && r.id.starts_with(c.as_str()))
+                    || (!r.id.is_empty() && c.starts_with(&r.id))
+            }
+            None => true,
+        })
+        .filter(|(d, _)| since.is_none_or(|s| *d >= s) && until.is_none_or(|u| *d <= u))
+        .filter(|(_, r)| match &opt.outcome {
+            Some(o) => outcome(r) == o,
+            None => true,
+        })
+        .collect();
+    records.sort_by_key(|(d, _)| *d);
+
+    // Restarts per container per day
+    let mut per_day: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for (d, r) in &records {
+        if matches!(outcome(r), "success" | "failed") {
+            *per_day
+                .entry((r.name.clone(), d.format("%Y-%m-%d").to_string()))
+                .or_insert(0) += 1;
+        }
+    }
+
+    // Per container incidents, success rate and mean time between failures
+    // Containers are grouped by stable identity where recorded so re-created containers accumulate
+    let mut by_container: HashMap<String, Vec<&(DateTime<FixedOffset>, JsonRecord)>> =
+        HashMap::new();
+    for entry in &records {
+        by_container
+            .entry(match entry.1.identity.is_empty() {
+                true => entry.1.name.clone(),
+                false => entry.1.identity.clone(),
+            })
+            .or_default()
+            .push(entry);
+    }
+    let mut containers: Vec<ContainerSummary> = by_container
+        .into_iter()
+        .map(|(name, entries)| {
+            let success = entries
+                .iter()
+                .filter(|(_, r)| outcome(r) == "success")
+                .count();
+            let failed = entries
+                .iter()
+                .filter(|(_, r)| outcome(r) == "failed")
+                .count();
+            let success_rate = match success + failed {
+                0 => None,
+                n => Some(success as f64 / n as f64 * 100.0),
+            };
+            let mtbf_seconds = match entries.len() {
+                0 | 1 => None,
+                n => Some((entries[n - 1].0 - entries[0].0).num_seconds() / (n as i64 - 1)),
+            };
+            // Compacted counts are keyed by identity and cannot be filtered by time
+            let compacted = entries
+                .iter()
+                .map(|(_, r)| record_key(r))
+                .collect::<std::collections::HashSet<String>>()
+                .iter()
+                .map(|id| summary.get(id).copied().unwrap_or(0))
+                .sum();
+            ContainerSummary {
+                name,
+                incidents: entries.len(),
+                success,
+                failed,
+                success_rate,
+                mtbf_seconds,
+                compacted,
+            }
+        })
+        .collect();
+    containers.sort_by(|a, b| b.incidents.cmp(&a.incidents).then(a.name.cmp(&b.name)));
+
+    let success: usize = containers.iter().map(|c| c.success).sum();
+    let failed: usize = containers.iter().map(|c| c.failed).sum();
+    let success_rate = match success + failed {
+        0 => None,
+        n => Some(success as f64 / n as f64 * 100.0),
+    };
+
+    if opt.format == "json" {
+        let output = json!({
+            "records": records.iter().map(|(_, r)| r).collect::<Vec<&JsonRecord>>(),
+            "restarts_per_day": per_day
+                .iter()
+                .map(|((name, day), count)| json!({"name": name, "date": day, "restarts": count}))
+                .collect::<Vec<_>>(),
+            "containers": containers,
+            "success_rate": success_rate,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    // Records
+    println!(
+        "{:<24} {:<24} {:<12} {:<8} {:<8} ERROR",
+        "DATE", "NAME", "ID", "CODE", "OUTCOME"
+    );
+    for (_, r) in &records {
+        println!(
+            "{:<24} {:<24} {:<12} {:<8} {:<8} {}",
+            r.date,
+            r.name,
+            r.id,
+            r.code,
+            outcome(r),
+            r.err
+        );
+    }
+    println!();
+
+    // Restarts per container per day
+    println!("{:<24} {:<12} RESTARTS", "NAME", "DATE");
+    for ((name, day), count) in &per_day {
+        println!("{:<24} {:<12} {}", name, day, count);
+    }
+    println!();
+
+    // Top offenders
+    println!(
+        "{:<24} {:<10} {:<8} {:<8} {:<10} {:<14} COMPACTED",
+        "NAME", "INCIDENTS", "SUCCESS", "FAILED", "RATE", "MTBF"
+    );
+    for c in containers.iter().take(10) {
+        println!(
+            "{:<24} {:<10} {:<8} {:<8} {:<10} {:<14} {}",
+            c.name,
+            c.incidents,
+            c.success,
+            c.failed,
+            c.success_rate
+                .map(|r| format!("{:.1}%", r))
+                .unwrap_or("-".to_string()),
+            c.mtbf_seconds
+                .map(format_duration)
+                .unwrap_or("-".to_string()),
+            c.compacted
+        );
+    }
+    println!();
+    println!(
+        "Success rate: {}",
+        success_rate
+            .map(|r| format!("{:.1}% ({} of {})", r, success, success + failed))
+            .unwrap_or("-".to_string())
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(action: &str, outcome: &str) -> JsonRecord {
+        serde_json::from_value(json!({
+            "date": "2026-10-19 12:00:00+0000",
+            "name": "app",
+            "id": "0123456789ab",
+            "code": 1,
+            "err": "",
+            "action": action,
+            "outcome": outcome,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn outcome_prefers_recorded_field() {
+        let cases = [
+            (
+                "[app (0123456789ab)] Container restart was successful",
+                "",
+                "success",
+            ),
+            (
+                "[app (0123456789ab)] Container restart failed: gone",
+                "",
+                "failed",
+            ),
+            ("Restart enabled: false", "", "disabled"),
+            (
+                "[app (0123456789ab)] Container stop was successful",
+                "",
+                "other",
+            ),
+            // The recorded outcome wins over the message
+            (
+                "[app (0123456789ab)] Project web restart failed: db",
+                "failed",
+                "failed",
+            ),
+            (
+                "[app (0123456789ab)] Container restart skipped on request as rule 1",
+                "other",
+                "other",
+            ),
+        ];
+        for (action, recorded, expected) in cases {
+            assert_eq!(outcome(&record(action, recorded)), expected, "{}", action);
+        }
+    }
+}
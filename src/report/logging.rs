@@ -1,7 +1,30 @@
-use super::record::{read_record, write_record, JsonRecord};
-use crate::{INFO, LOG_FILE, LOG_PATH, WARNING, YEAR};
+use super::diagnostics::write_diagnostics;
+use super::record::{
+    prune_artifacts, quarantine_record, read_record, read_summary, rotate_record, summary_key,
+    write_incident, write_record, JsonRecord, RetentionList,
+};
+use crate::{
+    DEBUG, DIAGNOSTICS_PATH, INCIDENT_PATH, INFO, LOG_FILE, QUARANTINE_FILE, SUMMARY_FILE, WARNING,
+    YEAR,
+};
+use bollard::Docker;
 use chrono::Local;
-use std::io::{stdout, Write};
+use std::{
+    collections::HashMap,
+    io::{stdout, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+// How often retention is applied while log.json is unchanged
+const ROTATE_INTERVAL: Duration = Duration::from_secs(3600);
+
+// When retention was last applied per log path, and the size and modification time of log.json
+type RotateStamp = (Instant, Option<(u64, SystemTime)>);
+static ROTATED: OnceLock<Mutex<HashMap<String, RotateStamp>>> = OnceLock::new();
 
 // Return information about the binary
 pub const NAME: &str = env!("CARGO_PKG_NAME");
@@ -28,35 +51,153 @@ pub fn print_version() {
     println!("https://www.gnu.org/licenses/gpl-3.0.html");
 }
 
+// Name of the Docker host being monitored when more than one is configured
+tokio::task_local! {
+    pub static ENDPOINT: String;
+}
+
+// Whether debug messages are written
+static LOG_DEBUG: AtomicBool = AtomicBool::new(false);
+
+pub fn set_log_debug(enable: bool) {
+    LOG_DEBUG.store(enable, Ordering::Relaxed);
+}
+
 // Logging
 pub async fn log_message(msg: &str, lvl: i8) {
+    if lvl == DEBUG && !LOG_DEBUG.load(Ordering::Relaxed) {
+        return;
+    }
     let date = Local::now().format("%Y-%m-%d %H:%M:%S%z").to_string();
     let level = match lvl {
         1 => "[WARNING]",
         2 => "[  ERROR]",
+        3 => "[  DEBUG]",
         _ => "[   INFO]",
     };
+    let endpoint = match ENDPOINT.try_with(|e| e.clone()) {
+        Ok(e) if !e.is_empty() => format!("[{}] ", e),
+        _ => "".to_string(),
+    };
     let mut lock = stdout().lock();
-    writeln!(lock, "{} {} {}", date, level, msg).ok();
+    writeln!(lock, "{} {} {}{}", date, level, endpoint, msg).ok();
 }
 
 // Write to log.json
-pub async fn log_write(data: JsonRecord) {
-    match write_record(data).await {
+pub async fn log_write(log_path: &str, data: JsonRecord) {
+    match write_record(log_path, data).await {
         Ok(()) => (),
         Err(e) => {
-            let msg0 = format!("Unable to write to log ({}{}): {}", LOG_PATH, LOG_FILE, e);
+            let msg0 = format!("Unable to write to log ({}{}): {}", log_path, LOG_FILE, e);
             log_message(&msg0, WARNING).await
         }
     }
 }
 
+// Write captured container log lines to the incidents directory
+pub async fn log_incident(log_path: &str, file_name: &str, lines: &[String]) {
+    match write_incident(log_path, file_name, lines).await {
+        Ok(()) => (),
+        Err(e) => {
+            let msg0 = format!(
+                "Unable to write incident ({}{}{}): {}",
+                log_path, INCIDENT_PATH, file_name, e
+            );
+            log_message(&msg0, WARNING).await
+        }
+    }
+}
+
+// Capture a diagnostics bundle, returning whether anything was written
+pub async fn log_diagnostics(
+    docker: &Docker,
+    log_path: &str,
+    bundle: &str,
+    id: &str,
+    export: bool,
+) -> bool {
+    match write_diagnostics(docker, log_path, bundle, id, export).await {
+        Ok(failures) => {
+            if !failures.is_empty() {
+                let msg0 = format!(
+                    "Incomplete diagnostics ({}{}{}): {}",
+                    log_path,
+                    DIAGNOSTICS_PATH,
+                    bundle,
+                    failures.join(", ")
+                );
+                log_message(&msg0, WARNING).await
+            }
+            true
+        }
+        Err(e) => {
+            let msg0 = format!(
+                "Unable to write diagnostics ({}{}{}): {}",
+                log_path, DIAGNOSTICS_PATH, bundle, e
+            );
+            log_message(&msg0, WARNING).await;
+            false
+        }
+    }
+}
+
 // Read from log.json
-pub async fn log_read(name: &str, id: String) {
-    match read_record().await {
-        Ok(records) => {
-            // Get unhealthy count for container
-            let action_count = records.into_iter().filter(|r| r.id == id).count();
+pub async fn log_read(
+    log_path: &str,
+    name: &str,
+    id: String,
+    identity: &str,
+    host: &str,
+    quarantine: bool,
+) {
+    match read_record(log_path).await {
+        Ok(set) => {
+            // Report lines that could not be deserialized
+            if !set.corrupt.is_empty() {
+                let lines: Vec<String> = set.corrupt.iter().map(|(n, _)| n.to_string()).collect();
+                let msg0 = format!(
+                    "Skipped {} corrupt line(s) in log ({}{}): {}",
+                    set.corrupt.len(),
+                    log_path,
+                    LOG_FILE,
+                    lines.join(",")
+                );
+                log_message(&msg0, WARNING).await;
+                if quarantine {
+                    log_quarantine(log_path).await;
+                }
+            }
+            // Get unhealthy count for container, including compacted history, where records
+            // that predate identity were compacted by name
+            let compacted = match read_summary(log_path).await {
+                Ok(summary) => {
+                    let count = |key: &str| summary.get(&summary_key(host, key)).copied();
+                    count(identity).unwrap_or(0)
+                        + match identity == name {
+                            true => 0,
+                            false => count(name).unwrap_or(0),
+                        }
+                }
+                Err(e) => {
+                    let msg0 = format!(
+                        "Unable to read from summary ({}{}): {}",
+                        log_path, SUMMARY_FILE, e
+                    );
+                    log_message(&msg0, WARNING).await;
+                    0
+                }
+            };
+            // Records that predate identity are matched on name or id
+            let action_count = set
+                .records
+                .into_iter()
+                .filter(|r| r.host.is_empty() || r.host == host)
+                .filter(|r| match r.identity.is_empty() {
+                    true => r.name == identity || r.id == identity,
+                    false => r.identity == identity,
+                })
+                .count() as u64
+                + compacted;
             // Report results
             let mut noun = "time";
             if action_count > 1 {
@@ -69,8 +210,110 @@ pub async fn log_read(name: &str, id: String) {
             log_message(&msg0, INFO).await;
         }
         Err(e) => {
-            let msg0 = format!("Unable to read from log ({}{}): {}", LOG_PATH, LOG_FILE, e);
+            let msg0 = format!("Unable to read from log ({}{}): {}", log_path, LOG_FILE, e);
+            log_message(&msg0, WARNING).await
+        }
+    }
+}
+
+// Move corrupt lines from log.json to log.json.quarantine
+async fn log_quarantine(log_path: &str) {
+    match quarantine_record(log_path).await {
+        Ok(count) => {
+            let msg0 = format!(
+                "Moved {} corrupt line(s) to quarantine ({}{})",
+                count, log_path, QUARANTINE_FILE
+            );
+            log_message(&msg0, INFO).await
+        }
+        Err(e) => {
+            let msg0 = format!(
+                "Unable to quarantine corrupt log lines ({}{}): {}",
+                log_path, QUARANTINE_FILE, e
+            );
             log_message(&msg0, WARNING).await
         }
     }
 }
+
+// Size and modification time of log.json
+async fn log_stamp(log_path: &str) -> Option<(u64, SystemTime)> {
+    let metadata = tokio::fs::metadata(log_path.to_owned() + LOG_FILE)
+        .await
+        .ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+// Whether retention is due: hourly, or once log.json has changed since last applied
+async fn rotate_due(log_path: &str) -> bool {
+    let stamp = log_stamp(log_path).await;
+    let rotated = ROTATED.get_or_init(|| Mutex::new(HashMap::new()));
+    match rotated.lock().unwrap().get(log_path) {
+        Some((at, last)) => at.elapsed() >= ROTATE_INTERVAL || *last != stamp,
+        None => true,
+    }
+}
+
+// Note retention was applied, with log.json as it was left
+async fn mark_rotated(log_path: &str) {
+    let stamp = log_stamp(log_path).await;
+    let rotated = ROTATED.get_or_init(|| Mutex::new(HashMap::new()));
+    rotated
+        .lock()
+        .unwrap()
+        .insert(log_path.to_string(), (Instant::now(), stamp));
+}
+
+// Apply retention to log.json, its rotated files, incident files and diagnostics bundles
+pub async fn log_rotate(log_path: &str, retention: &RetentionList) {
+    if !rotate_due(log_path).await {
+        return;
+    }
+    match rotate_record(log_path, retention).await {
+        Ok(()) => (),
+        Err(e) => {
+            let msg0 = format!("Unable to rotate log ({}{}): {}", log_path, LOG_FILE, e);
+            log_message(&msg0, WARNING).await
+        }
+    }
+    match prune_artifacts(log_path, retention).await {
+        Ok(0) => (),
+        Ok(n) => {
+            let msg0 = format!("Removed {} expired incident file(s) and bundle(s)", n);
+            log_message(&msg0, DEBUG).await
+        }
+        Err(e) => {
+            let msg0 = format!(
+                "Unable to remove expired incident files and bundles ({}): {}",
+                log_path, e
+            );
+            log_message(&msg0, WARNING).await
+        }
+    }
+    mark_rotated(log_path).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rotate_due_when_log_changes() {
+        let path =
+            std::env::temp_dir().join(format!("docker-autoheal-rotate-{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&path).await;
+        tokio::fs::create_dir_all(&path).await.unwrap();
+        let path = format!("{}/", path.display());
+
+        assert!(rotate_due(&path).await);
+        mark_rotated(&path).await;
+        assert!(!rotate_due(&path).await);
+        tokio::fs::write(path.clone() + LOG_FILE, b"{}\n")
+            .await
+            .unwrap();
+        assert!(rotate_due(&path).await);
+        mark_rotated(&path).await;
+        assert!(!rotate_due(&path).await);
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+}
@@ -1,53 +1,724 @@
-use crate::{LOG_FILE, LOG_PATH};
+use crate::{
+    inquire::inspect::HealthLogList, DIAGNOSTICS_PATH, INCIDENT_PATH, LOCK_FILE, LOG_FILE,
+    QUARANTINE_FILE, SUMMARY_FILE,
+};
+use chrono::{DateTime, Duration, Local};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tokio::fs::File;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Error};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, Error};
 
 #[derive(Serialize, Deserialize)]
 pub struct JsonRecord {
     pub date: String,
     pub name: String,
     pub id: String,
+    #[serde(default)]
+    pub identity: String,
+    #[serde(default)]
+    pub host: String,
     pub code: i64,
     pub err: String,
     pub action: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub health: Vec<HealthLogList>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub incident: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub diagnostics: String,
+    // One of ALLOWED_HISTORY_OUTCOMES; empty in records written before it was recorded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub outcome: String,
 }
 
-pub async fn read_record() -> Result<Vec<JsonRecord>, Error> {
-    let log_file = LOG_PATH.to_owned() + LOG_FILE;
+#[derive(Clone)]
+pub struct RetentionList {
+    pub max_age: u64,
+    pub max_size: u64,
+    pub max_files: u64,
+    pub max_artifacts: u64,
+    pub compress: bool,
+}
+
+pub struct RecordSet {
+    pub records: Vec<JsonRecord>,
+    // Line number and raw content of lines that failed to deserialize or are not valid UTF-8
+    pub corrupt: Vec<(usize, Vec<u8>)>,
+}
+
+// Ensure log_path exists and is writeable, creating it where permitted
+pub async fn probe_record(log_path: &str) -> Result<(), Error> {
+    tokio::fs::create_dir_all(log_path).await?;
+    // The lock file is required for persistence, so opening it doubles as the probe
+    File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(log_path.to_owned() + LOCK_FILE)
+        .await?;
+    Ok(())
+}
+
+// Serialize access to log.json across autoheal instances sharing a volume
+// The lock is held on a dedicated file as log.json itself is renamed on rotation
+// and released when the returned handle is dropped
+async fn lock_record(log_path: &str, exclusive: bool) -> Result<std::fs::File, Error> {
+    let lock_file = log_path.to_owned() + LOCK_FILE;
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_file)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            let operation = match exclusive {
+                true => libc::LOCK_EX,
+                false => libc::LOCK_SH,
+            };
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = exclusive;
+        Ok(file)
+    })
+    .await?
+}
+
+// Deserialize a raw line into the set, keeping lines that fail as they are
+fn parse_line(set: &mut RecordSet, line_number: usize, line: &[u8]) {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return;
+    }
+    // serde_json rejects invalid UTF-8, so such lines are corrupt too
+    match serde_json::from_slice(line) {
+        Ok(record) => set.records.push(record),
+        Err(_) => set.corrupt.push((line_number, line.to_vec())),
+    };
+}
+
+// Read log.json without locking; callers must hold the lock
+async fn read_lines(log_path: &str) -> Result<RecordSet, Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
     // Open file
-    let mut file = File::open(log_file).await?;
-    let file_clone = file.try_clone().await?;
-    let reader = BufReader::new(file_clone);
-    // Initialize an empty vector to store the records
-    let mut records = Vec::new();
-    // Create a stream from the reader lines
-    let mut lines = reader.lines();
-    // Build array of results
-    while let Ok(Some(line)) = lines.next_line().await {
-        match serde_json::from_str(&line) {
-            Ok(record) => records.push(record),
-            Err(e) => return Err(e.into()),
+    let file = File::open(log_file).await?;
+    let mut reader = BufReader::new(file);
+    let mut set = RecordSet {
+        records: Vec::new(),
+        corrupt: Vec::new(),
+    };
+    // Read raw lines, as a partial write may have left bytes that are not UTF-8
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        line_number += 1;
+        parse_line(&mut set, line_number, &line);
+        line.clear();
+    }
+    Ok(set)
+}
+
+// Take a shared lock for reading without write access, e.g. from the history subcommand
+// Reading proceeds unlocked where the lock file is missing or cannot be opened
+async fn lock_read_only(log_path: &str) -> Result<Option<std::fs::File>, Error> {
+    let lock_file = log_path.to_owned() + LOCK_FILE;
+    tokio::task::spawn_blocking(move || {
+        let Ok(file) = std::fs::File::open(lock_file) else {
+            return Ok(None);
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH) } != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(Some(file))
+    })
+    .await?
+}
+
+pub async fn read_record(log_path: &str) -> Result<RecordSet, Error> {
+    let _lock = lock_record(log_path, false).await?;
+    read_lines(log_path).await
+}
+
+// Read rotated log.json.n files, oldest first, followed by log.json
+pub async fn read_history(log_path: &str) -> Result<RecordSet, Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
+    let _lock = lock_read_only(log_path).await?;
+    // Gather rotated files by their sequence number
+    let mut rotated: Vec<(u64, String)> = Vec::new();
+    let mut entries = tokio::fs::read_dir(log_path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if let Some(n) = file_name
+            .strip_prefix(&format!("{}.", LOG_FILE))
+            .map(|n| n.trim_end_matches(".gz"))
+            .and_then(|n| n.parse().ok())
+        {
+            rotated.push((n, log_path.to_owned() + &file_name));
+        }
+    }
+    rotated.sort_by_key(|r| std::cmp::Reverse(r.0));
+
+    let mut set = RecordSet {
+        records: Vec::new(),
+        corrupt: Vec::new(),
+    };
+    for (_, path) in rotated {
+        let content = tokio::fs::read(&path).await?;
+        let content = match path.ends_with(".gz") {
+            true => {
+                tokio::task::spawn_blocking(move || {
+                    let mut decoded = Vec::new();
+                    std::io::Read::read_to_end(&mut GzDecoder::new(&content[..]), &mut decoded)?;
+                    Ok::<Vec<u8>, Error>(decoded)
+                })
+                .await??
+            }
+            false => content,
         };
+        for (n, line) in content.split(|b| *b == b'\n').enumerate() {
+            parse_line(&mut set, n + 1, line);
+        }
+    }
+    if tokio::fs::metadata(&log_file).await.is_ok() {
+        let current = read_lines(log_path).await?;
+        set.records.extend(current.records);
+        set.corrupt.extend(current.corrupt);
+    }
+    Ok(set)
+}
+
+// Move corrupt lines from log.json to the quarantine file
+pub async fn quarantine_record(log_path: &str) -> Result<usize, Error> {
+    let _lock = lock_record(log_path, true).await?;
+    let set = read_lines(log_path).await?;
+    if set.corrupt.is_empty() {
+        return Ok(0);
     }
+    let quarantine_file = log_path.to_owned() + QUARANTINE_FILE;
+    let mut data = Vec::new();
+    for (_, line) in &set.corrupt {
+        data.extend_from_slice(line);
+        data.push(b'\n');
+    }
+    let mut file = File::options()
+        .append(true)
+        .create(true)
+        .open(quarantine_file)
+        .await?;
+    file.write_all(&data).await?;
     file.flush().await?;
-    Ok(records)
+    replace_record(log_path, &set.records, &[]).await?;
+    Ok(set.corrupt.len())
 }
 
-pub async fn write_record(data: JsonRecord) -> Result<(), Error> {
-    let log_file = LOG_PATH.to_owned() + LOG_FILE;
+pub async fn write_record(log_path: &str, data: JsonRecord) -> Result<(), Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
     // Serialize the data to JSON
     let mut json_data = serde_json::to_string(&data)?;
     // Append newline to the JSON data
     json_data.push('\n');
+    let _lock = lock_record(log_path, true).await?;
     // Asynchronously open file for writing
     let mut file = File::options()
+        .read(true)
         .append(true)
         .create(true)
         .open(log_file)
         .await?;
-    // Write the JSON data to file
+    // Terminate a partial last line so the record does not join it
+    if file.metadata().await?.len() > 0 {
+        let mut last = [0u8; 1];
+        file.seek(std::io::SeekFrom::End(-1)).await?;
+        file.read_exact(&mut last).await?;
+        if last[0] != b'\n' {
+            json_data.insert(0, '\n');
+        }
+    }
+    // Write the JSON data to file in a single append
     file.write_all(json_data.as_bytes()).await?;
     file.flush().await?;
     Ok(())
 }
+
+// Save captured container log lines as a per-incident file
+pub async fn write_incident(
+    log_path: &str,
+    file_name: &str,
+    lines: &[String],
+) -> Result<(), Error> {
+    let incident_path = log_path.to_owned() + INCIDENT_PATH;
+    tokio::fs::create_dir_all(&incident_path).await?;
+    let mut content = lines.join("\n");
+    content.push('\n');
+    tokio::fs::write(incident_path + file_name, content).await
+}
+
+// Key compacted counts by host and stable identity
+pub fn summary_key(host: &str, identity: &str) -> String {
+    match host.is_empty() {
+        true => identity.to_string(),
+        false => format!("{}|{}", host, identity),
+    }
+}
+
+// Key records by stable identity, falling back to name for records that predate it, as the
+// identity they would have had by default
+pub fn record_key(record: &JsonRecord) -> String {
+    match record.identity.is_empty() {
+        true => summary_key(&record.host, &record.name),
+        false => summary_key(&record.host, &record.identity),
+    }
+}
+
+// Read compacted per-container counts from summary.json
+pub async fn read_summary(log_path: &str) -> Result<HashMap<String, u64>, Error> {
+    let summary_file = log_path.to_owned() + SUMMARY_FILE;
+    match tokio::fs::read_to_string(summary_file).await {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Fold records into summary.json so historical counts survive retention
+async fn compact_records(log_path: &str, records: &[JsonRecord]) -> Result<(), Error> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let summary_file = log_path.to_owned() + SUMMARY_FILE;
+    let mut summary = read_summary(log_path).await?;
+    for record in records {
+        *summary.entry(record_key(record)).or_insert(0) += 1;
+    }
+    // Write to a temporary file and rename so a crash cannot truncate the summary
+    let tmp_file = format!("{}.tmp", summary_file);
+    tokio::fs::write(&tmp_file, serde_json::to_string(&summary)?).await?;
+    tokio::fs::rename(tmp_file, summary_file).await
+}
+
+// Replace log.json with the provided records, preserving any corrupt lines as-is
+async fn replace_record(
+    log_path: &str,
+    records: &[JsonRecord],
+    corrupt: &[(usize, Vec<u8>)],
+) -> Result<(), Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
+    let mut json_data = Vec::new();
+    for (_, line) in corrupt {
+        json_data.extend_from_slice(line);
+        json_data.push(b'\n');
+    }
+    for record in records {
+        serde_json::to_writer(&mut json_data, record)?;
+        json_data.push(b'\n');
+    }
+    let tmp_file = format!("{}.tmp", log_file);
+    tokio::fs::write(&tmp_file, json_data).await?;
+    tokio::fs::rename(tmp_file, log_file).await
+}
+
+// Shift log.json.n to log.json.n+1, discarding anything beyond max_files
+async fn shift_record(log_path: &str, max_files: u64, compress: bool) -> Result<(), Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
+    let suffix = match compress {
+        true => ".gz",
+        false => "",
+    };
+    for n in (1..=max_files).rev() {
+        // Rotated files may have been written with or without compression
+        for ext in ["", ".gz"] {
+            let from = format!("{}.{}{}", log_file, n, ext);
+            if tokio::fs::metadata(&from).await.is_err() {
+                continue;
+            }
+            if n == max_files {
+                tokio::fs::remove_file(&from).await?;
+            } else {
+                tokio::fs::rename(&from, format!("{}.{}{}", log_file, n + 1, ext)).await?;
+            }
+        }
+    }
+    if max_files == 0 {
+        return tokio::fs::remove_file(log_file).await;
+    }
+    let rotated = format!("{}.1{}", log_file, suffix);
+    if compress {
+        let content = tokio::fs::read(&log_file).await?;
+        let compressed = tokio::task::spawn_blocking(move || {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            std::io::Write::write_all(&mut encoder, &content)?;
+            encoder.finish()
+        })
+        .await??;
+        tokio::fs::write(rotated, compressed).await?;
+        tokio::fs::remove_file(log_file).await
+    } else {
+        tokio::fs::rename(log_file, rotated).await
+    }
+}
+
+// Remove incident log files and diagnostics bundles older than max_age days or beyond the
+// newest max_artifacts of each, returning how many were removed
+pub async fn prune_artifacts(log_path: &str, retention: &RetentionList) -> Result<usize, Error> {
+    let cutoff = std::time::SystemTime::now()
+        - std::time::Duration::from_secs(retention.max_age * 24 * 60 * 60);
+    let mut removed = 0;
+    for dir in [INCIDENT_PATH, DIAGNOSTICS_PATH] {
+        let mut entries = match tokio::fs::read_dir(log_path.to_owned() + dir).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut artifacts = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            artifacts.push((metadata.modified()?, metadata.is_dir(), entry.path()));
+        }
+        // Newest first
+        artifacts.sort_by_key(|a| std::cmp::Reverse(a.0));
+        for (n, (modified, is_dir, path)) in artifacts.into_iter().enumerate() {
+            let expired = retention.max_age > 0 && modified < cutoff;
+            let excess = retention.max_artifacts > 0 && n as u64 >= retention.max_artifacts;
+            if !(expired || excess) {
+                continue;
+            }
+            match is_dir {
+                true => tokio::fs::remove_dir_all(path).await?,
+                false => tokio::fs::remove_file(path).await?,
+            }
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Remove rotated log.json.N files last written more than max_age days ago
+async fn expire_rotated(log_path: &str, max_age: u64) -> Result<(), Error> {
+    let cutoff =
+        std::time::SystemTime::now() - std::time::Duration::from_secs(max_age * 24 * 60 * 60);
+    let mut entries = tokio::fs::read_dir(log_path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rotated = name
+            .strip_prefix(LOG_FILE)
+            .and_then(|n| n.strip_prefix('.'))
+            .map(|n| n.strip_suffix(".gz").unwrap_or(n))
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+        if rotated && entry.metadata().await?.modified()? < cutoff {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+// Apply age and size retention to log.json, and age retention to its rotated files
+pub async fn rotate_record(log_path: &str, retention: &RetentionList) -> Result<(), Error> {
+    let log_file = log_path.to_owned() + LOG_FILE;
+    let _lock = lock_record(log_path, true).await?;
+    if retention.max_age > 0 {
+        expire_rotated(log_path, retention.max_age).await?;
+    }
+    if tokio::fs::metadata(&log_file).await.is_err() {
+        return Ok(());
+    }
+
+    // Compact records older than max_age days
+    if retention.max_age > 0 {
+        let cutoff = Local::now() - Duration::days(retention.max_age as i64);
+        let set = read_lines(log_path).await?;
+        let (expired, retained): (Vec<JsonRecord>, Vec<JsonRecord>) =
+            set.records.into_iter().partition(|r| {
+                match DateTime::parse_from_str(&r.date, "%Y-%m-%d %H:%M:%S%z") {
+                    Ok(date) => date < cutoff,
+                    Err(_) => false,
+                }
+            });
+        if !expired.is_empty() {
+            compact_records(log_path, &expired).await?;
+            replace_record(log_path, &retained, &set.corrupt).await?;
+        }
+    }
+
+    // Rotate once log.json exceeds max_size megabytes
+    if retention.max_size > 0 {
+        let size = tokio::fs::metadata(&log_file).await?.len();
+        if size >= retention.max_size * 1024 * 1024 {
+            compact_records(log_path, &read_lines(log_path).await?.records).await?;
+            shift_record(log_path, retention.max_files, retention.compress).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fresh directory per test, as log_path is expected to end in /
+    async fn test_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "docker-autoheal-record-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = tokio::fs::remove_dir_all(&path).await;
+        tokio::fs::create_dir_all(&path).await.unwrap();
+        format!("{}/", path.display())
+    }
+
+    fn test_record(name: &str, date: &str) -> JsonRecord {
+        JsonRecord {
+            date: date.to_string(),
+            name: name.to_string(),
+            id: format!("{}-id", name),
+            identity: "".to_string(),
+            host: "".to_string(),
+            code: 0,
+            err: "".to_string(),
+            action: "restart".to_string(),
+            health: vec![],
+            logs: vec![],
+            incident: "".to_string(),
+            diagnostics: "".to_string(),
+            outcome: "success".to_string(),
+        }
+    }
+
+    fn today() -> String {
+        Local::now().format("%Y-%m-%d %H:%M:%S%z").to_string()
+    }
+
+    #[tokio::test]
+    async fn read_lines_keeps_corrupt_lines_raw() {
+        let path = test_path("corrupt").await;
+        let mut content = serde_json::to_vec(&test_record("a", &today())).unwrap();
+        content.extend_from_slice(b"\n{\"name\": \"trunc\n\n");
+        content.extend_from_slice(b"\xff\xfe{}\n");
+        tokio::fs::write(path.clone() + LOG_FILE, &content)
+            .await
+            .unwrap();
+
+        let set = read_record(&path).await.unwrap();
+        assert_eq!(set.records.len(), 1);
+        assert_eq!(
+            set.corrupt,
+            vec![
+                (2, b"{\"name\": \"trunc".to_vec()),
+                (4, b"\xff\xfe{}".to_vec()),
+            ]
+        );
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_record_terminates_partial_line() {
+        let path = test_path("partial").await;
+        tokio::fs::write(path.clone() + LOG_FILE, b"{\"name\": \"trunc")
+            .await
+            .unwrap();
+
+        write_record(&path, test_record("a", &today()))
+            .await
+            .unwrap();
+        let set = read_record(&path).await.unwrap();
+        assert_eq!(set.records.len(), 1);
+        assert_eq!(set.records[0].name, "a");
+        assert_eq!(set.corrupt, vec![(1, b"{\"name\": \"trunc".to_vec())]);
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn quarantine_record_moves_corrupt_lines() {
+        let path = test_path("quarantine").await;
+        write_record(&path, test_record("a", &today()))
+            .await
+            .unwrap();
+        let mut file = File::options()
+            .append(true)
+            .open(path.clone() + LOG_FILE)
+            .await
+            .unwrap();
+        file.write_all(b"not json\n\xff\n").await.unwrap();
+        write_record(&path, test_record("b", &today()))
+            .await
+            .unwrap();
+
+        assert_eq!(quarantine_record(&path).await.unwrap(), 2);
+        let set = read_record(&path).await.unwrap();
+        let names: Vec<&str> = set.records.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(set.corrupt.is_empty());
+        let quarantined = tokio::fs::read(path.clone() + QUARANTINE_FILE)
+            .await
+            .unwrap();
+        assert_eq!(quarantined, b"not json\n\xff\n");
+        // Nothing left to move
+        assert_eq!(quarantine_record(&path).await.unwrap(), 0);
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_history_does_not_create_lock() {
+        let path = test_path("history").await;
+        let mut content = serde_json::to_vec(&test_record("a", &today())).unwrap();
+        content.push(b'\n');
+        tokio::fs::write(path.clone() + LOG_FILE, &content)
+            .await
+            .unwrap();
+
+        let set = read_history(&path).await.unwrap();
+        assert_eq!(set.records.len(), 1);
+        assert_eq!(set.records[0].outcome, "success");
+        assert!(tokio::fs::metadata(path.clone() + LOCK_FILE).await.is_err());
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotate_record_compacts_expired_records() {
+        let path = test_path("age").await;
+        write_record(&path, test_record("old", "2000-01-01 00:00:00+0000"))
+            .await
+            .unwrap();
+        write_record(&path, test_record("new", &today()))
+            .await
+            .unwrap();
+        let retention = RetentionList {
+            max_age: 30,
+            max_size: 0,
+            max_files: 0,
+            max_artifacts: 0,
+            compress: false,
+        };
+
+        rotate_record(&path, &retention).await.unwrap();
+        let set = read_record(&path).await.unwrap();
+        assert_eq!(set.records.len(), 1);
+        assert_eq!(set.records[0].name, "new");
+        let summary = read_summary(&path).await.unwrap();
+        assert_eq!(summary.get("old"), Some(&1));
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotate_record_shifts_by_size() {
+        let path = test_path("size").await;
+        // Just over the 1 MB threshold
+        let mut record = test_record("a", &today());
+        record.err = "x".repeat(1024 * 1024);
+        write_record(&path, record).await.unwrap();
+        let retention = RetentionList {
+            max_age: 0,
+            max_size: 1,
+            max_files: 2,
+            max_artifacts: 0,
+            compress: true,
+        };
+
+        rotate_record(&path, &retention).await.unwrap();
+        assert!(tokio::fs::metadata(path.clone() + LOG_FILE).await.is_err());
+        assert!(tokio::fs::metadata(path.clone() + "log.json.1.gz")
+            .await
+            .is_ok());
+
+        write_record(&path, test_record("b", &today()))
+            .await
+            .unwrap();
+        let set = read_history(&path).await.unwrap();
+        let names: Vec<&str> = set.records.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(set.corrupt.is_empty());
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotate_record_expires_rotated_files() {
+        let path = test_path("rotated").await;
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(40 * 24 * 60 * 60);
+        for name in ["log.json.1", "log.json.2.gz", "log.json.3"] {
+            let file = std::fs::File::create(path.clone() + name).unwrap();
+            if name != "log.json.1" {
+                file.set_modified(old).unwrap();
+            }
+        }
+        std::fs::File::create(path.clone() + QUARANTINE_FILE)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let retention = RetentionList {
+            max_age: 30,
+            max_size: 0,
+            max_files: 5,
+            max_artifacts: 0,
+            compress: false,
+        };
+
+        rotate_record(&path, &retention).await.unwrap();
+        for (name, kept) in [
+            ("log.json.1", true),
+            ("log.json.2.gz", false),
+            ("log.json.3", false),
+            (QUARANTINE_FILE, true),
+        ] {
+            let exists = tokio::fs::metadata(path.clone() + name).await.is_ok();
+            assert_eq!(exists, kept, "{}", name);
+        }
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prune_artifacts_applies_age_and_count() {
+        let path = test_path("artifacts").await;
+        let incidents = path.clone() + INCIDENT_PATH;
+        let bundle = path.clone() + DIAGNOSTICS_PATH + "20000101-000000-a-0123456789ab";
+        tokio::fs::create_dir_all(&incidents).await.unwrap();
+        tokio::fs::create_dir_all(&bundle).await.unwrap();
+        tokio::fs::write(bundle.clone() + "/inspect.json", b"{}")
+            .await
+            .unwrap();
+        let now = std::time::SystemTime::now();
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        for (name, age) in [
+            ("old.log", 40),
+            ("older.log", 2),
+            ("newer.log", 1),
+            ("new.log", 0),
+        ] {
+            let file = std::fs::File::create(incidents.clone() + name).unwrap();
+            file.set_modified(now - day * age).unwrap();
+        }
+        std::fs::File::open(&bundle)
+            .unwrap()
+            .set_modified(now - day * 40)
+            .unwrap();
+        let retention = RetentionList {
+            max_age: 30,
+            max_size: 0,
+            max_files: 0,
+            max_artifacts: 2,
+            compress: false,
+        };
+
+        assert_eq!(prune_artifacts(&path, &retention).await.unwrap(), 3);
+        let mut kept = vec![];
+        let mut entries = tokio::fs::read_dir(&incidents).await.unwrap();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            kept.push(entry.file_name().to_string_lossy().to_string());
+        }
+        kept.sort();
+        assert_eq!(kept, vec!["new.log", "newer.log"]);
+        assert!(tokio::fs::metadata(&bundle).await.is_err());
+        tokio::fs::remove_dir_all(&path).await.unwrap();
+    }
+}